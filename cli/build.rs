@@ -0,0 +1,39 @@
+//! Records the commit and target the binary is built from, for
+//! `ssort --version --verbose`.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Packagers building from a tarball can set the commit themselves
+    println!("cargo:rerun-if-env-changed=SSORT_GIT_COMMIT");
+    let commit = env::var("SSORT_GIT_COMMIT").ok().or_else(git_commit);
+    println!(
+        "cargo:rustc-env=SSORT_GIT_COMMIT={}",
+        commit.as_deref().unwrap_or("unknown")
+    );
+
+    for path in ["../.git/HEAD", "../.git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    let target = env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=SSORT_TARGET={}", target);
+    let profile = env::var("PROFILE").unwrap_or_default();
+    println!("cargo:rustc-env=SSORT_PROFILE={}", profile);
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_string())
+}
@@ -0,0 +1,72 @@
+//! `ssort comm`: compare two inputs line by line in suffix order.
+
+use crate::InputFormat;
+use std::cmp::Ordering;
+use std::io;
+use std::io::Write;
+use suffixsort::SortConfig;
+
+#[derive(clap::Args, Debug)]
+pub struct CommArgs {
+    /// suppress lines only in the first input
+    #[arg(short = '1')]
+    suppress_first: bool,
+
+    /// suppress lines only in the second input
+    #[arg(short = '2')]
+    suppress_second: bool,
+
+    /// suppress lines in both inputs
+    #[arg(short = '3')]
+    suppress_common: bool,
+
+    /// the two inputs (use '-' for stdin)
+    #[arg(value_names = ["FILE1", "FILE2"], num_args = 2, required = true)]
+    pub files: Vec<String>,
+}
+
+/// Sorts both inputs and writes, like `comm`, lines only in the first input
+/// in column 1, lines only in the second in column 2 and lines in both in
+/// column 3, with columns indented by tabs. Lines are the same when their
+/// keys compare equal.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &CommArgs,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let (first, _) = config.process_lines(crate::read_input(&args.files[..1], format)?);
+    let (second, _) = config.process_lines(crate::read_input(&args.files[1..], format)?);
+    let mut first = first.into_iter().peekable();
+    let mut second = second.into_iter().peekable();
+
+    let shown = [
+        !args.suppress_first,
+        !args.suppress_second,
+        !args.suppress_common,
+    ];
+
+    loop {
+        let ordering = match (first.peek(), second.peek()) {
+            (Some(a), Some(b)) => config.compare_keys(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        let (column, line) = match ordering {
+            Ordering::Less => (0, first.next()),
+            Ordering::Greater => (1, second.next()),
+            Ordering::Equal => {
+                second.next();
+                (2, first.next())
+            }
+        };
+
+        if shown[column] {
+            let indent = shown[..column].iter().filter(|&&shown| shown).count();
+            let line = line.expect("peeked line");
+            writeln!(out, "{}{}", "\t".repeat(indent), line.original)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! `ssort endswith`: print the lines whose key ends with a suffix.
+
+use crate::InputFormat;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use suffixsort::SortConfig;
+use suffixsort::index::SuffixIndex;
+
+#[derive(clap::Args, Debug)]
+pub struct EndsWithArgs {
+    /// the ending to look for (prepared like keys, e.g. case-folded)
+    #[arg(allow_hyphen_values = true)]
+    suffix: String,
+
+    /// look the suffix up in an index saved earlier instead of reading input
+    #[arg(long = "index", value_name = "FILE", conflicts_with = "files")]
+    pub index: Option<String>,
+
+    /// also save an index of the input to FILE, for later lookups with --index
+    #[arg(long = "save-index", value_name = "FILE", conflicts_with = "index")]
+    save_index: Option<String>,
+
+    /// input files (use '-' for stdin, default if no files provided)
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Writes the lines whose key ends with the suffix, in suffix order, saving
+/// the index first with `--save-index` through a buffer of `buffer` bytes.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &EndsWithArgs,
+    buffer: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let index = match &args.index {
+        Some(path) => {
+            let file = File::open(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", path, e)))?;
+            SuffixIndex::read_from(file).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("'{}': {}", path, e))
+            })?
+        }
+        None => config.build_index(crate::read_input(&args.files, format)?),
+    };
+    if let Some(path) = &args.save_index {
+        crate::write_atomically(path, buffer, |out| Ok(index.write_to(out)?))?;
+        log::info!("saved an index of {} lines to {}", index.len(), path);
+    }
+
+    let found = index.ends_with(&args.suffix);
+    log::info!(
+        "{} of {} lines end with {:?}",
+        found.len(),
+        index.len(),
+        args.suffix
+    );
+    if config.reverse {
+        for line in found.iter().rev() {
+            writeln!(out, "{}", line)?;
+        }
+    } else {
+        for line in found {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
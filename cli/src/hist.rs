@@ -0,0 +1,87 @@
+//! `ssort hist`: a textual histogram of how many lines share each key.
+
+use crate::InputFormat;
+use crate::unique::{self, UniqueBy};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io;
+use std::io::Write;
+use suffixsort::{ProcessedLine, SortConfig};
+
+#[derive(clap::Args, Debug)]
+pub struct HistArgs {
+    /// group by the last K characters of the key instead of the whole key
+    #[arg(
+        long = "suffix",
+        value_name = "K",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    suffix: Option<u64>,
+
+    /// length of the longest bar
+    #[arg(long = "width", value_name = "COLUMNS", default_value_t = 50)]
+    width: usize,
+
+    /// input files (use '-' for stdin, default if no files provided)
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Sorts the input and writes one bar per group of equal keys (or key
+/// endings), in suffix order.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &HistArgs,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let lines = crate::read_input(&args.files, format)?;
+    let (processed, _) = config.process_lines(lines);
+
+    let groups = match args.suffix {
+        Some(k) => count_endings(&processed, k as usize),
+        None => unique::groups(config, UniqueBy::Key, processed.into_iter().map(Ok))
+            .map(|group| group.map(|group| (group[0].key.clone(), group.len())))
+            .collect::<io::Result<Vec<_>>>()?,
+    };
+
+    let max_count = groups.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let key_width = groups
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+    let count_width = max_count.to_string().len();
+
+    for (key, count) in groups {
+        let bar = (count * args.width).div_ceil(max_count);
+        writeln!(
+            out,
+            "{:>key_width$} {:>count_width$} {}",
+            key,
+            count,
+            "#".repeat(bar)
+        )?;
+    }
+    Ok(())
+}
+
+/// Counts the lines by the last `k` characters of their keys, in order of
+/// each ending's first line. Lines sharing an ending need not be adjacent,
+/// e.g. with `--by-length` or `--forward`, so they are looked up by ending.
+fn count_endings(processed: &[ProcessedLine], k: usize) -> Vec<(String, usize)> {
+    let mut groups: Vec<(String, usize)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for p in processed {
+        let start = p.key.chars().count().saturating_sub(k);
+        let ending: String = p.key.chars().skip(start).collect();
+        match positions.entry(ending) {
+            Entry::Occupied(entry) => groups[*entry.get()].1 += 1,
+            Entry::Vacant(entry) => {
+                groups.push((entry.key().clone(), 1));
+                entry.insert(groups.len() - 1);
+            }
+        }
+    }
+    groups
+}
@@ -0,0 +1,102 @@
+//! `--format html`: sorted lines as an HTML list or table, with the key of
+//! each line in a `<span class="key">` for styling.
+
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::io;
+use std::io::Write;
+use suffixsort::ProcessedLine;
+
+/// How sorted lines are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One line per line, as read
+    Text,
+    /// An ordered list (`<ol class="ssort">`)
+    Html,
+    /// A table (`<table class="ssort">`) with the key in the first column
+    HtmlTable,
+}
+
+/// Writes what goes before the first line.
+pub fn write_start(out: &mut impl Write, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => Ok(()),
+        OutputFormat::Html => writeln!(out, "<ol class=\"ssort\">"),
+        OutputFormat::HtmlTable => writeln!(out, "<table class=\"ssort\">"),
+    }
+}
+
+/// Writes what goes after the last line.
+pub fn write_end(out: &mut impl Write, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => Ok(()),
+        OutputFormat::Html => writeln!(out, "</ol>"),
+        OutputFormat::HtmlTable => writeln!(out, "</table>"),
+    }
+}
+
+/// Writes `text`, the output for the sorted line `p` (or a line passed
+/// through unsorted if `None`), as a list item or table row, after `prefix`
+/// (the file name with `--with-filename`), which the key positions of `p`
+/// do not count.
+pub fn write_line(
+    out: &mut impl Write,
+    format: OutputFormat,
+    p: Option<&ProcessedLine>,
+    prefix: &str,
+    text: &str,
+) -> io::Result<()> {
+    let line = match p.and_then(|p| p.visual_start.zip(p.word_length)) {
+        Some((start, length)) => {
+            // Positions are in characters
+            let start = byte_offset(text, start);
+            let end = start + byte_offset(&text[start..], length);
+            format!(
+                "{}<span class=\"key\">{}</span>{}",
+                escape(&text[..start]),
+                escape(&text[start..end]),
+                escape(&text[end..])
+            )
+        }
+        None => escape(text).into_owned(),
+    };
+
+    match format {
+        OutputFormat::Text => unreachable!("plain text is not written as HTML"),
+        OutputFormat::Html => writeln!(out, "<li>{}{}</li>", escape(prefix), line),
+        OutputFormat::HtmlTable => writeln!(
+            out,
+            "<tr><td class=\"key\">{}</td><td>{}{}</td></tr>",
+            escape(p.map_or("", |p| &p.key)),
+            escape(prefix),
+            line
+        ),
+    }
+}
+
+/// The byte offset of character `chars` of `text`, or its end.
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
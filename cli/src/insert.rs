@@ -0,0 +1,109 @@
+//! `ssort insert`: add lines to an already sorted file.
+
+use crate::InputFormat;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use suffixsort::SortConfig;
+
+#[derive(clap::Args, Debug)]
+pub struct InsertArgs {
+    /// the sorted file to insert into; it is rewritten unless --output is given
+    #[arg(long = "into", value_name = "FILE", required = true)]
+    pub into: String,
+
+    /// insert into the small sorted file FILE.delta instead, merging it into FILE once it is large
+    #[arg(long = "delta")]
+    delta: bool,
+
+    /// merge FILE.delta into FILE once it has N lines
+    #[arg(
+        long = "compact-at",
+        value_name = "N",
+        default_value_t = 10000,
+        requires = "delta"
+    )]
+    compact_at: usize,
+
+    /// the lines to insert (use '-' for stdin, default if no files provided)
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// The file next to `path` that `--delta` inserts into.
+pub fn delta_path(path: &str) -> String {
+    format!("{}.delta", path)
+}
+
+/// Sorts the new lines and merges them into the sorted file (or its delta),
+/// so only the new lines are sorted and the file is read once. The file must
+/// be sorted with the same options; new lines go after existing lines with
+/// equal keys.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &InsertArgs,
+    output: Option<&str>,
+    buffer: usize,
+) -> io::Result<()> {
+    // Inserting never drops lines, from the file or the new ones
+    let config = SortConfig {
+        filter: None,
+        exclude_no_word: false,
+        ..config.clone()
+    };
+    let (new, _) = config.process_lines(crate::read_input(&args.files, format)?);
+    let new: Vec<String> = new.into_iter().map(|line| line.original).collect();
+    log::info!("inserting {} lines into {}", new.len(), args.into);
+
+    if !args.delta {
+        let sorted = crate::read_input(std::slice::from_ref(&args.into), format)?;
+        return crate::write_atomically(output.unwrap_or(&args.into), buffer, |out| {
+            write_merged(&config, format, [sorted, new], out)
+        });
+    }
+
+    if output.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--output cannot be used with insert --delta",
+        ));
+    }
+    let delta_path = delta_path(&args.into);
+    let delta = match Path::new(&delta_path).exists() {
+        true => crate::read_input(std::slice::from_ref(&delta_path), format)?,
+        false => Vec::new(),
+    };
+    let delta: Vec<String> = config.merge([delta, new]).collect();
+
+    if delta.len() < args.compact_at {
+        return crate::write_atomically(&delta_path, buffer, |out| {
+            write_merged(&config, format, [delta], out)
+        });
+    }
+    log::info!(
+        "merging {} lines of {} into {}",
+        delta.len(),
+        delta_path,
+        args.into
+    );
+    let sorted = crate::read_input(std::slice::from_ref(&args.into), format)?;
+    crate::write_atomically(&args.into, buffer, |out| {
+        write_merged(&config, format, [sorted, delta], out)
+    })?;
+    match std::fs::remove_file(&delta_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(io::Error::new(e.kind(), format!("'{}': {}", delta_path, e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_merged<const N: usize>(
+    config: &SortConfig,
+    format: &InputFormat,
+    inputs: [Vec<String>; N],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    crate::write_records(out, format, config.merge(inputs))
+}
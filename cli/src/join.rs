@@ -0,0 +1,90 @@
+//! `ssort join`: combine lines of two inputs that have equal keys.
+
+use crate::InputFormat;
+use crate::unique::{self, UniqueBy};
+use std::cmp::Ordering;
+use std::io;
+use std::io::Write;
+use suffixsort::SortConfig;
+
+#[derive(clap::Args, Debug)]
+pub struct JoinArgs {
+    /// output template: {1} and {2} are the lines of the first and second input, {key} their key
+    #[arg(long = "format", value_name = "TEMPLATE", default_value = "{1}\t{2}")]
+    format: String,
+
+    /// the two inputs (use '-' for stdin)
+    #[arg(value_names = ["FILE1", "FILE2"], num_args = 2, required = true)]
+    pub files: Vec<String>,
+}
+
+/// Sorts both inputs and writes one line per pair of lines with equal keys
+/// (every combination if a key occurs more than once), in suffix order.
+/// Lines without a partner are left out.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &JoinArgs,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let (first, _) = config.process_lines(crate::read_input(&args.files[..1], format)?);
+    let (second, _) = config.process_lines(crate::read_input(&args.files[1..], format)?);
+    let mut first = unique::groups(config, UniqueBy::Key, first.into_iter().map(Ok)).peekable();
+    let mut second = unique::groups(config, UniqueBy::Key, second.into_iter().map(Ok)).peekable();
+
+    loop {
+        let (a, b) = match (first.peek(), second.peek()) {
+            (Some(Ok(a)), Some(Ok(b))) => (a, b),
+            // Stop at a read error rather than leave the output truncated
+            (Some(Err(_)), _) => return first.next().transpose().map(drop),
+            (_, Some(Err(_))) => return second.next().transpose().map(drop),
+            _ => return Ok(()),
+        };
+        match config.compare_keys(&a[0], &b[0]) {
+            Ordering::Less => {
+                first.next();
+            }
+            Ordering::Greater => {
+                second.next();
+            }
+            Ordering::Equal => {
+                for a in a {
+                    for b in b {
+                        let fields = [
+                            ("{key}", &a.key),
+                            ("{1}", &a.original),
+                            ("{2}", &b.original),
+                        ];
+                        writeln!(out, "{}", expand(&args.format, &fields))?;
+                    }
+                }
+                first.next();
+                second.next();
+            }
+        }
+    }
+}
+
+/// Replaces the placeholders in `template` in a single pass, so placeholders
+/// inside the lines themselves are left alone.
+fn expand(template: &str, fields: &[(&str, &String)]) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+
+    'scan: while let Some(i) = rest.find('{') {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        for (placeholder, value) in fields {
+            if let Some(after) = rest.strip_prefix(placeholder) {
+                expanded.push_str(value);
+                rest = after;
+                continue 'scan;
+            }
+        }
+        expanded.push('{');
+        rest = &rest[1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
@@ -0,0 +1,41 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Prints log records from ssort and the suffixsort library to stderr.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("ssort: error: {}", record.args()),
+            Level::Warn => eprintln!("ssort: warning: {}", record.args()),
+            _ => eprintln!("ssort: {}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger. Warnings are shown by default; `quiet` limits output
+/// to errors and `verbose` adds progress and code-path details.
+pub fn init(quiet: bool, verbose: bool) {
+    let level = if quiet {
+        LevelFilter::Error
+    } else if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Warn
+    };
+
+    // Only fails if a logger is already set, which cannot happen here
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
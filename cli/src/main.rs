@@ -1,14 +1,64 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
+use std::borrow::Cow;
 use std::fs::File;
 use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::IsTerminal;
 use std::io::Write;
-use suffixsort::{PaddingInfo, ProcessedLine, SortConfig};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{self, AtomicBool};
+use std::time::{Duration, Instant};
+use suffixsort::{
+    FoldMode, NoWordPosition, PaddingInfo, PhaseTimings, Phonetic, ProcessedLine, SortAlgorithm,
+    SortBy, SortConfig, Tiebreak, WordClass,
+};
+
+mod comm;
+mod csv_mode;
+mod endswith;
+mod hist;
+mod html;
+mod insert;
+mod join;
+mod logger;
+mod merge;
+mod meta;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod records;
+mod saved;
+#[cfg(feature = "scripting")]
+mod script;
+mod sections;
+mod setops;
+mod spill;
+mod term;
+mod unique;
+mod version;
+mod watch;
+
+use html::OutputFormat;
+use rayon::prelude::*;
+use regex::Regex;
+use sections::Section;
+use setops::SetOp;
+use spill::{Sorted, SpillSorter};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+use unicode_width::UnicodeWidthStr;
+use unique::{CountFormat, Keep, UniqueBy};
 
 #[derive(Parser, Debug)]
 #[command(
+    group(clap::ArgGroup::new("key_field").args(["field_separator", "csv", "key_after_last"]).multiple(true)),
+    group(clap::ArgGroup::new("one_per_group").args(["unique", "count", "union", "intersect", "difference"]).multiple(true)),
+    group(clap::ArgGroup::new("duplicates").args(["unique", "count", "only_duplicates", "only_unique", "union", "intersect", "difference"]).multiple(true)),
+    group(clap::ArgGroup::new("set_op").args(["union", "intersect", "difference"]).requires("files").conflicts_with_all(["max_memory", "section_marker", "lines", "each"])),
     version,
+    disable_version_flag = true,
     about = "ssort: inverse lexicographic (suffix) sort by first word (default) or whole line",
     long_about = r#"
 ssort: inverse lexicographic (suffix) sort by first word (default) or whole line
@@ -30,6 +80,15 @@ struct Args {
     #[arg(short = 'l', long = "line", help_heading = "Sorting Options")]
     use_entire_line: bool,
 
+    /// ignore leading blanks of the line with -l
+    #[arg(
+        short = 'b',
+        long = "ignore-leading-blanks",
+        requires = "use_entire_line",
+        help_heading = "Sorting Options"
+    )]
+    ignore_leading_blanks: bool,
+
     /// dictionary order: ignore non-alphabetic characters when finding first word
     #[arg(
         short = 'd',
@@ -38,11 +97,138 @@ struct Args {
     )]
     dictionary_order: bool,
 
+    /// characters allowed inside a word with -d besides letters
+    #[arg(
+        long = "word-chars",
+        value_name = "CHARS",
+        default_value = "-",
+        allow_hyphen_values = true,
+        requires = "dictionary_order",
+        help_heading = "Sorting Options"
+    )]
+    word_chars: String,
+
+    /// characters that make up a word with -d: alpha, alnum, or a regex class like '[a-z0-9_]'
+    #[arg(
+        long = "word-class",
+        value_name = "CLASS",
+        value_parser = parse_word_class,
+        requires = "dictionary_order",
+        help_heading = "Sorting Options"
+    )]
+    word_class: Option<WordClass>,
+
+    /// find words with Unicode (UAX #29) word boundaries (handles contractions and CJK)
+    #[arg(
+        long = "unicode-words",
+        conflicts_with_all = ["use_entire_line", "word_chars", "word_class"],
+        help_heading = "Sorting Options"
+    )]
+    unicode_words: bool,
+
+    /// use the first N words, joined by single spaces, as the key
+    #[arg(
+        long = "words",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "use_entire_line",
+        help_heading = "Sorting Options"
+    )]
+    words: u64,
+
+    /// use bytes OFFSET..OFFSET+LEN of each line as the key (0-based, no word detection)
+    #[arg(
+        long = "byte-key",
+        value_name = "OFFSET,LEN",
+        value_parser = parse_byte_key,
+        conflicts_with_all = ["use_entire_line", "dictionary_order", "words"],
+        help_heading = "Sorting Options"
+    )]
+    byte_key: Option<Range<usize>>,
+
+    /// split lines on SEP and use a field (see --field) as the key
+    #[arg(
+        short = 't',
+        long = "field-separator",
+        value_name = "SEP",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        conflicts_with_all = ["use_entire_line", "dictionary_order", "words", "byte_key"],
+        help_heading = "Sorting Options"
+    )]
+    field_separator: Option<String>,
+
+    /// parse the input as CSV (delimiter from -t, default ',') and key on a field (see --field)
+    #[arg(
+        long = "csv",
+        conflicts_with_all = ["use_entire_line", "dictionary_order", "words", "byte_key", "unicode_words"],
+        help_heading = "Sorting Options"
+    )]
+    csv: bool,
+
+    /// field number (from 1) used as the key with --field-separator
+    #[arg(
+        long = "field",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "key_field",
+        help_heading = "Sorting Options"
+    )]
+    field: u64,
+
+    /// use the last field as the key with --field-separator
+    #[arg(
+        long = "last-field",
+        requires = "key_field",
+        conflicts_with = "field",
+        help_heading = "Sorting Options"
+    )]
+    last_field: bool,
+
+    /// compare the key field as a whole in ordinary order instead of suffix-wise
+    #[arg(
+        long = "field-compare",
+        requires = "key_field",
+        help_heading = "Sorting Options"
+    )]
+    field_compare: bool,
+
+    /// use everything after the last DELIM as the key, e.g. '/' for file names (like -t DELIM --last-field)
+    #[arg(
+        long = "key-after-last",
+        value_name = "DELIM",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        conflicts_with_all = ["field_separator", "csv", "field", "last_field", "use_entire_line", "dictionary_order", "words", "byte_key"],
+        help_heading = "Sorting Options"
+    )]
+    key_after_last: Option<String>,
+
+    /// compare keys in ordinary left-to-right order instead of from the end
+    #[arg(long = "forward", help_heading = "Sorting Options")]
+    forward: bool,
+
+    /// compare a number at the end of the key numerically first (file2 before file10), then the rest of the key
+    #[arg(long = "trailing-numbers", help_heading = "Sorting Options")]
+    trailing_numbers: bool,
+
+    /// compare the key's extension (after the last '.') first, then the name before it
+    #[arg(long = "by-extension", help_heading = "Sorting Options")]
+    by_extension: bool,
+
+    /// split the key on '.' and compare the parts from the last one (com, example, www), each left to right
+    #[arg(
+        long = "domain",
+        conflicts_with_all = ["forward", "field_compare", "trailing_numbers", "by_extension"],
+        help_heading = "Sorting Options"
+    )]
+    domain: bool,
+
     /// reverse the sort order
     #[arg(short = 'r', long, help_heading = "Sorting Options")]
     reverse: bool,
 
-    /// stable sort (maintains original order of equal elements)
+    /// stable sort (keep equal keys in input order instead of comparing whole lines)
     #[arg(short = 's', long, help_heading = "Sorting Options")]
     stable: bool,
 
@@ -50,10 +236,94 @@ struct Args {
     #[arg(short = 'a', long = "right-align", help_heading = "Output")]
     right_align: bool,
 
+    /// right-align lines to the terminal's right edge ($COLUMNS or 80 columns when not a terminal)
+    #[arg(
+        long = "align-right-edge",
+        conflicts_with = "right_align",
+        help_heading = "Output"
+    )]
+    align_right_edge: bool,
+
+    /// pad lines so the Nth (default first) occurrence of CHAR lines up, e.g. '=' or ':,2'
+    #[arg(
+        long = "align-on",
+        value_name = "CHAR[,N]",
+        value_parser = parse_align_on,
+        conflicts_with_all = ["right_align", "align_right_edge", "max_memory"],
+        help_heading = "Output"
+    )]
+    align_on: Option<AlignOn>,
+
+    /// print each line's sort key right-aligned in a column before the line
+    #[arg(
+        long = "show-key",
+        conflicts_with_all = ["word_only", "right_align", "align_right_edge", "align_on", "max_memory"],
+        help_heading = "Output"
+    )]
+    show_key: bool,
+
+    /// what separates the key column from the line with --show-key
+    #[arg(
+        long = "show-key-separator",
+        value_name = "SEP",
+        default_value = " | ",
+        requires = "show_key",
+        help_heading = "Output"
+    )]
+    show_key_separator: String,
+
+    /// prefix each line with the name of the file it came from, like grep -H
+    #[arg(
+        short = 'H',
+        long = "with-filename",
+        conflicts_with_all = ["max_memory", "section_marker", "lines", "load_index", "check"],
+        help_heading = "Output"
+    )]
+    with_filename: bool,
+
+    /// what separates the file name from the line with --with-filename
+    #[arg(
+        long = "filename-separator",
+        value_name = "SEP",
+        default_value = ":",
+        requires = "with_filename",
+        help_heading = "Output"
+    )]
+    filename_separator: String,
+
+    /// sort each input file on its own and write the results one after another under a header naming the file
+    #[arg(
+        long = "group-by-file",
+        conflicts_with_all = ["max_memory", "section_marker", "lines", "load_index", "set_op", "check", "with_filename"],
+        help_heading = "Output"
+    )]
+    group_by_file: bool,
+
+    /// write plain lines, or an HTML list or table with each key in a <span class="key">
+    #[arg(
+        long = "format",
+        conflicts_with_all = ["word_only", "right_align", "align_right_edge", "align_on", "show_key"],
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        help_heading = "Output"
+    )]
+    format: OutputFormat,
+
     /// exclude lines without words
     #[arg(short = 'x', long = "exclude-no-word", help_heading = "Output")]
     exclude_no_word: bool,
 
+    /// where lines without words go: first, last, or drop them like -x
+    #[arg(
+        long = "no-word-position",
+        value_name = "WHERE",
+        value_enum,
+        conflicts_with = "exclude_no_word",
+        help_heading = "Output"
+    )]
+    no_word_position: Option<NoWordArg>,
+
     /// output only the word used for sorting (excludes the remainder of lines)
     #[arg(short = 'w', long = "word-only", help_heading = "Output")]
     word_only: bool,
@@ -61,109 +331,1868 @@ struct Args {
     /// normalize unicode to NFC form
     #[arg(short = 'n', long = "normalize", help_heading = "Sorting Options")]
     normalize: bool,
-}
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+    /// write lines in NFC form instead of as they were read (needs --normalize)
+    #[arg(
+        long = "write-normalized",
+        requires = "normalize",
+        help_heading = "Output"
+    )]
+    write_normalized: bool,
 
-    // Read input from files or stdin
-    let lines = read_input(&args.files)?;
+    /// write lines case-folded like keys with -i (with -n, folded as by -i -n)
+    #[arg(long = "output-folded", help_heading = "Output")]
+    output_folded: bool,
 
-    // Create config for the library
-    let config = SortConfig {
-        ignore_case: args.ignore_case,
-        use_entire_line: args.use_entire_line,
-        dictionary_order: args.dictionary_order,
-        reverse: args.reverse,
-        stable: args.stable,
-        right_align: args.right_align,
-        exclude_no_word: args.exclude_no_word,
-        word_only: args.word_only,
-        normalize: args.normalize,
-    };
+    /// collapse runs of whitespace in the key to a single space
+    #[arg(long = "squeeze-ws", help_heading = "Sorting Options")]
+    squeeze_ws: bool,
 
-    // Process and sort lines using the library
-    let (processed, padding_info) = config.process_lines(lines);
+    /// strip trailing whitespace from the key
+    #[arg(long = "trim-key", help_heading = "Sorting Options")]
+    trim_key: bool,
 
-    // Write results
-    write_output(processed, padding_info, args.word_only, args.right_align)
+    /// ignore control characters in the key
+    #[arg(long = "ignore-nonprinting", help_heading = "Sorting Options")]
+    ignore_nonprinting: bool,
+
+    /// transliterate Latin letters in the key to ASCII, so "Łódź" sorts with "Lodz"
+    #[arg(long = "ascii-key", help_heading = "Sorting Options")]
+    ascii_key: bool,
+
+    /// ignore symbols and emoji in the key
+    #[arg(long = "ignore-symbols", help_heading = "Sorting Options")]
+    ignore_symbols: bool,
+
+    /// compare keys by how they sound: rhyme (English vowel and consonant sounds), metaphone or soundex
+    #[arg(
+        long = "phonetic",
+        value_name = "ALGORITHM",
+        value_enum,
+        help_heading = "Sorting Options"
+    )]
+    phonetic: Option<PhoneticArg>,
+
+    /// extract keys from lines with ANSI escape sequences (colors) removed
+    #[arg(long = "strip-ansi", help_heading = "Sorting Options")]
+    strip_ansi: bool,
+
+    /// remove ANSI escape sequences from the output too (implies --strip-ansi)
+    #[arg(long = "strip-ansi-output", help_heading = "Output")]
+    strip_ansi_output: bool,
+
+    /// use radix sort on the reversed key bytes (often faster for large ASCII inputs)
+    #[arg(long = "radix", help_heading = "Sorting Options")]
+    radix: bool,
+
+    /// sort by integer ids of the distinct keys (often faster when many lines share few keys)
+    #[arg(
+        long = "intern-keys",
+        conflicts_with = "radix",
+        help_heading = "Sorting Options"
+    )]
+    intern_keys: bool,
+
+    /// order by key length (shortest first), then by suffix
+    #[arg(long = "by-length", help_heading = "Sorting Options")]
+    by_length: bool,
+
+    /// order by how many lines share the key (most common first), then by suffix
+    #[arg(
+        long = "by-frequency",
+        conflicts_with_all = ["by_length", "max_memory"],
+        help_heading = "Sorting Options"
+    )]
+    by_frequency: bool,
+
+    /// order by the length of the whole line in characters (shortest first), then by suffix
+    #[arg(
+        long = "by-line-length",
+        conflicts_with_all = ["by_length", "by_frequency"],
+        help_heading = "Sorting Options"
+    )]
+    by_line_length: bool,
+
+    /// like --by-line-length, but count display columns (wide characters count as two)
+    #[arg(
+        long = "by-line-width",
+        conflicts_with_all = ["by_length", "by_frequency", "by_line_length"],
+        help_heading = "Sorting Options"
+    )]
+    by_line_width: bool,
+
+    /// order by the estimated number of syllables in the key (fewest first), then by suffix
+    #[arg(
+        long = "by-syllables",
+        conflicts_with_all = ["by_length", "by_frequency", "by_line_length", "by_line_width"],
+        help_heading = "Sorting Options"
+    )]
+    by_syllables: bool,
+
+    /// last-resort order for equal keys without -s: input order, the whole line read forwards or suffix-wise, or the rest of the line without the key read forwards
+    #[arg(
+        long = "tiebreak",
+        value_name = "MODE",
+        value_enum,
+        default_value_t = TiebreakArg::Line,
+        help_heading = "Sorting Options"
+    )]
+    tiebreak: TiebreakArg,
+
+    /// derive each line's key with a rhai script (the line is in `line`)
+    #[cfg(feature = "scripting")]
+    #[arg(
+        long = "key-script",
+        value_name = "FILE",
+        conflicts_with_all = ["deterministic", "csv"],
+        help_heading = "Sorting Options"
+    )]
+    key_script: Option<String>,
+
+    /// derive each line's key with a WebAssembly plugin exporting `extract_key`
+    #[cfg(feature = "plugins")]
+    #[arg(
+        long = "plugin",
+        value_name = "FILE",
+        conflicts_with = "csv",
+        help_heading = "Sorting Options"
+    )]
+    plugin: Option<String>,
+
+    /// limit memory use to SIZE (e.g. 512M, 2G), spilling sorted runs to temporary files
+    #[arg(long = "max-memory", value_name = "SIZE", value_parser = spill::parse_size)]
+    max_memory: Option<usize>,
+
+    /// write the result to FILE instead of stdout (FILE may also be an input)
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FILE",
+        help_heading = "Output"
+    )]
+    output: Option<String>,
+
+    /// buffer SIZE bytes of output (e.g. 64K, 1M) before writing it out
+    #[arg(
+        long = "write-buffer",
+        value_name = "SIZE",
+        value_parser = spill::parse_size,
+        default_value = "128K",
+        help_heading = "Output"
+    )]
+    write_buffer: usize,
+
+    /// also save the sorted lines to FILE, so --load-index can output them without sorting again
+    #[arg(
+        long = "save-index",
+        value_name = "FILE",
+        conflicts_with_all = ["max_memory", "section_marker", "lines", "each", "set_op", "check"],
+        help_heading = "Output"
+    )]
+    save_index: Option<String>,
+
+    /// output the lines saved by --save-index (with the same sort options) instead of reading input
+    #[arg(
+        long = "load-index",
+        value_name = "FILE",
+        conflicts_with_all = ["files", "save_index", "max_memory", "section_marker", "lines", "each", "watch", "set_op", "check"],
+        help_heading = "Output"
+    )]
+    load_index: Option<String>,
+
+    /// sort each input file independently, writing the result next to it (see --output-template)
+    #[arg(long = "each", requires = "files", help_heading = "Output")]
+    each: bool,
+
+    /// output path for --each; '{}' is replaced by the input path
+    #[arg(
+        long = "output-template",
+        value_name = "TEMPLATE",
+        default_value = "{}.sorted",
+        requires = "each",
+        help_heading = "Output"
+    )]
+    output_template: String,
+
+    /// keep running and re-sort whenever an input file changes (needs --output or --each)
+    #[arg(long = "watch", requires = "files", help_heading = "Output")]
+    watch: bool,
+
+    /// guarantee byte-identical output across runs and thread counts: the comparison sort, whole-line tie-breaks and serial rendering (rejects --key-script, --radix, --intern-keys and --tiebreak)
+    #[arg(long = "deterministic", conflicts_with_all = ["radix", "intern_keys", "tiebreak"])]
+    deterministic: bool,
+
+    /// only sort and output lines matching REGEX
+    #[arg(long = "filter", value_name = "REGEX", value_parser = Regex::new)]
+    filter: Option<Regex>,
+
+    /// keep lines matching REGEX out of the sort (see --excluded)
+    #[arg(long = "exclude-pattern", value_name = "REGEX", value_parser = Regex::new)]
+    exclude_pattern: Option<Regex>,
+
+    /// what to do with lines matching --exclude-pattern: drop them, or pass them through unsorted
+    #[arg(
+        long = "excluded",
+        value_name = "WHERE",
+        value_enum,
+        default_value_t = ExcludedPlacement::Drop,
+        requires = "exclude_pattern"
+    )]
+    excluded: ExcludedPlacement,
+
+    /// output only the first of each group of duplicate lines (see --unique-by)
+    #[arg(short = 'u', long = "unique", help_heading = "Output")]
+    unique: bool,
+
+    /// like -u, but prefix each line with the number of lines in its group
+    #[arg(
+        long = "count",
+        conflicts_with = "right_align",
+        help_heading = "Output"
+    )]
+    count: bool,
+
+    /// how --count shows group sizes: a prefix, a tab-separated suffix column, or a percentage
+    #[arg(
+        long = "count-format",
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = CountFormat::Prefix,
+        requires = "count",
+        help_heading = "Output"
+    )]
+    count_format: CountFormat,
+
+    /// leave out groups of duplicates with fewer than N lines
+    #[arg(
+        long = "min-count",
+        value_name = "N",
+        default_value_t = 1,
+        requires = "duplicates",
+        help_heading = "Output"
+    )]
+    min_count: usize,
+
+    /// output only lines that have duplicates; with -u, one line per such group
+    #[arg(long = "only-duplicates", help_heading = "Output")]
+    only_duplicates: bool,
+
+    /// output only lines whose key (or line, see --unique-by) occurs once
+    #[arg(
+        long = "only-unique",
+        conflicts_with = "only_duplicates",
+        help_heading = "Output"
+    )]
+    only_unique: bool,
+
+    /// output one line per key found in any input file
+    #[arg(long = "union", help_heading = "Output")]
+    union: bool,
+
+    /// output one line per key found in every input file
+    #[arg(long = "intersect", help_heading = "Output")]
+    intersect: bool,
+
+    /// output one line per key found in the first input file but in none of the others
+    #[arg(long = "difference", help_heading = "Output")]
+    difference: bool,
+
+    /// what makes lines duplicates for -u: an equal key, or the identical line
+    #[arg(
+        long = "unique-by",
+        value_name = "WHAT",
+        value_enum,
+        default_value_t = UniqueBy::Key,
+        requires = "duplicates",
+        help_heading = "Output"
+    )]
+    unique_by: UniqueBy,
+
+    /// which line of a group of duplicates -u keeps, in sorted order
+    #[arg(
+        long = "keep",
+        value_enum,
+        default_value_t = Keep::First,
+        requires = "one_per_group",
+        help_heading = "Output"
+    )]
+    keep: Keep,
+
+    /// only check that the input is sorted; report the first disorder and exit with status 1
+    #[arg(
+        short = 'c',
+        long = "check",
+        conflicts_with_all = ["output", "each", "watch", "max_memory", "section_marker", "lines", "duplicates"],
+        help_heading = "Output"
+    )]
+    check: bool,
+
+    /// leave out the first N sorted lines
+    #[arg(
+        long = "skip",
+        value_name = "N",
+        default_value_t = 0,
+        conflicts_with_all = ["section_marker", "lines", "group_by_file"],
+        help_heading = "Output"
+    )]
+    skip: usize,
+
+    /// output at most N sorted lines (after --skip)
+    #[arg(
+        long = "take",
+        value_name = "N",
+        conflicts_with_all = ["section_marker", "lines", "group_by_file"],
+        help_heading = "Output"
+    )]
+    take: Option<usize>,
+
+    /// sort blank-line-separated paragraphs as records, keyed by their first line
+    #[arg(long = "paragraphs", conflicts_with = "csv")]
+    paragraphs: bool,
+
+    /// sort blocks of lines separated by lines equal to SEP (e.g. '%' for fortune files)
+    #[arg(
+        long = "record-sep",
+        value_name = "SEP",
+        conflicts_with_all = ["csv", "paragraphs"]
+    )]
+    record_sep: Option<String>,
+
+    /// sort only the lines between lines matching REGEX, keeping those in place
+    #[arg(
+        long = "section-marker",
+        value_name = "REGEX",
+        value_parser = Regex::new,
+        conflicts_with = "max_memory"
+    )]
+    section_marker: Option<Regex>,
+
+    /// sort only lines START to END (from 1, inclusive; either may be omitted), passing the rest through
+    #[arg(
+        long = "lines",
+        value_name = "START:END",
+        value_parser = parse_line_range,
+        conflicts_with_all = ["max_memory", "section_marker"]
+    )]
+    lines: Option<Range<usize>>,
+
+    /// read from stdin even when it is a terminal
+    #[arg(long = "force-stdin")]
+    force_stdin: bool,
+
+    /// warn about and skip input lines that are not valid UTF-8 instead of failing
+    #[arg(long = "skip-bad-lines")]
+    skip_bad_lines: bool,
+
+    /// warn about and skip input files that are missing or cannot be opened instead of failing
+    #[arg(long = "ignore-missing")]
+    ignore_missing: bool,
+
+    /// read the input file names from FILE, one per line ('-' reads the list from stdin)
+    #[arg(
+        long = "files-from",
+        value_name = "FILE",
+        conflicts_with_all = ["files", "load_index"]
+    )]
+    files_from: Option<String>,
+
+    /// expand wildcards (*, ?, [...]) in input file names, for shells that do not (always on for Windows)
+    #[arg(long = "glob")]
+    glob: bool,
+
+    /// what to do with directories among the inputs: fail, skip them, or read the files in them
+    #[arg(
+        long = "directories",
+        value_name = "ACTION",
+        value_enum,
+        default_value_t = Directories::Error
+    )]
+    directories: Directories,
+
+    /// suppress warnings
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        conflicts_with = "verbose",
+        help_heading = "Diagnostics"
+    )]
+    quiet: bool,
+
+    /// report files read, dropped lines and chosen code paths on stderr
+    #[arg(short = 'v', long = "verbose", help_heading = "Diagnostics")]
+    verbose: bool,
+
+    /// print the version; with -v also the commit, target and features it was built with
+    #[arg(short = 'V', long = "version", help_heading = "Diagnostics")]
+    version: bool,
+
+    /// report time spent reading, preparing keys, sorting and writing on stderr
+    #[arg(long = "time", help_heading = "Diagnostics")]
+    time: bool,
+
+    /// write the options, line counts, padding and timings used as JSON to FILE (stderr without FILE)
+    #[arg(
+        long = "emit-meta",
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        help_heading = "Diagnostics"
+    )]
+    emit_meta: Option<Option<String>>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn read_input(files: &[String]) -> io::Result<Vec<String>> {
-    if files.is_empty() {
-        // Read from stdin
-        io::stdin().lock().lines().collect()
-    } else {
-        // Read from files
-        let mut lines = Vec::new();
-        for filename in files {
-            if filename == "-" {
-                // Read from stdin
-                lines.extend(io::stdin().lock().lines().collect::<Result<Vec<_>, _>>()?);
-            } else {
-                // Read from file
-                let file = File::open(filename).map_err(|e| {
-                    io::Error::new(io::ErrorKind::NotFound, format!("'{}': {}", filename, e))
-                })?;
-                let reader = BufReader::new(file);
-                lines.extend(reader.lines().collect::<Result<Vec<_>, _>>()?);
-            }
-        }
-        Ok(lines)
-    }
+/// Reports built on the sorted input instead of the sorted lines, and ways of
+/// updating sorted files; the sorting options given before the command still
+/// apply.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// print a histogram of how many lines share each key, in suffix order
+    Hist(hist::HistArgs),
+    /// compare two inputs: lines only in the first, only in the second, and in both
+    Comm(comm::CommArgs),
+    /// combine the lines of two inputs that have equal keys
+    Join(join::JoinArgs),
+    /// print only the lines whose key ends with SUFFIX
+    Endswith(endswith::EndsWithArgs),
+    /// add lines to a sorted file without sorting it again
+    Insert(insert::InsertArgs),
+    /// merge inputs that are already sorted, without sorting them again
+    Merge(merge::MergeArgs),
 }
 
-fn write_output(
-    processed: Vec<ProcessedLine>,
-    padding_info: Option<PaddingInfo>,
-    word_only: bool,
-    right_align: bool,
-) -> io::Result<()> {
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
+impl Command {
+    /// The input files, or `None` if the command reads no input.
+    fn files(&self) -> Option<&[String]> {
+        match self {
+            Command::Hist(args) => Some(&args.files),
+            Command::Comm(args) => Some(&args.files),
+            Command::Join(args) => Some(&args.files),
+            Command::Endswith(args) if args.index.is_some() => None,
+            Command::Endswith(args) => Some(&args.files),
+            Command::Insert(args) => Some(&args.files),
+            Command::Merge(args) => Some(&args.files),
+        }
+    }
 
-    if word_only {
-        // Output only the word used for sorting
-        if right_align {
-            let max_key_len = processed
-                .iter()
-                .map(|p| p.key.chars().count())
-                .max()
-                .unwrap_or(0);
+    /// Runs the command, writing to `output` (or stdout) through a buffer of
+    /// `buffer` bytes unless it updates files itself.
+    fn run(
+        &self,
+        config: &SortConfig,
+        format: &InputFormat,
+        output: Option<&str>,
+        buffer: usize,
+    ) -> io::Result<()> {
+        match self {
+            Command::Hist(args) => write_to(output, buffer, |mut out| {
+                hist::run(config, format, args, &mut out)
+            }),
+            Command::Comm(args) => write_to(output, buffer, |mut out| {
+                comm::run(config, format, args, &mut out)
+            }),
+            Command::Join(args) => write_to(output, buffer, |mut out| {
+                join::run(config, format, args, &mut out)
+            }),
+            Command::Endswith(args) => write_to(output, buffer, |mut out| {
+                endswith::run(config, format, args, buffer, &mut out)
+            }),
+            Command::Insert(args) => insert::run(config, format, args, output, buffer),
+            Command::Merge(args) => write_to(output, buffer, |mut out| {
+                merge::run(config, format, args, &mut out)
+            }),
+        }
+    }
+}
 
-            for p in processed {
-                let padding = " ".repeat(max_key_len.saturating_sub(p.key.chars().count()));
-                writeln!(handle, "{}{}", padding, p.key)?;
-            }
+impl Args {
+    fn set_op(&self) -> Option<SetOp> {
+        if self.union {
+            Some(SetOp::Union)
+        } else if self.intersect {
+            Some(SetOp::Intersect)
+        } else if self.difference {
+            Some(SetOp::Difference)
         } else {
-            for p in processed {
-                writeln!(handle, "{}", p.key)?;
-            }
+            None
         }
-    } else if let Some(padding_info) = padding_info {
-        for p in processed {
-            if padding_info.use_end_pos {
-                // Dictionary order with right-align - use end position of first word
-                if let (Some(visual_start), Some(word_length)) = (p.visual_start, p.word_length) {
-                    let end_pos = visual_start + word_length;
-                    let padding = " ".repeat(padding_info.max_value.saturating_sub(end_pos));
-                    writeln!(handle, "{}{}", padding, p.original)?;
-                } else {
-                    // Line has no word, output without padding
-                    writeln!(handle, "{}", p.original)?;
-                }
-            } else {
-                // Other modes
-                let padding =
-                    " ".repeat(padding_info.max_value.saturating_sub(p.key.chars().count()));
-                writeln!(handle, "{}{}", padding, p.original)?;
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TiebreakArg {
+    Index,
+    Line,
+    LineSuffix,
+    Rest,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PhoneticArg {
+    Rhyme,
+    Metaphone,
+    Soundex,
+}
+
+impl From<PhoneticArg> for Phonetic {
+    fn from(arg: PhoneticArg) -> Self {
+        match arg {
+            PhoneticArg::Rhyme => Phonetic::Rhyme,
+            PhoneticArg::Metaphone => Phonetic::Metaphone,
+            PhoneticArg::Soundex => Phonetic::Soundex,
+        }
+    }
+}
+
+impl From<TiebreakArg> for Tiebreak {
+    fn from(arg: TiebreakArg) -> Self {
+        match arg {
+            TiebreakArg::Index => Tiebreak::Index,
+            TiebreakArg::Line => Tiebreak::Line,
+            TiebreakArg::LineSuffix => Tiebreak::LineSuffix,
+            TiebreakArg::Rest => Tiebreak::Rest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum NoWordArg {
+    First,
+    Last,
+    Drop,
+}
+
+impl From<NoWordArg> for NoWordPosition {
+    fn from(arg: NoWordArg) -> Self {
+        match arg {
+            NoWordArg::First => NoWordPosition::First,
+            NoWordArg::Last => NoWordPosition::Last,
+            NoWordArg::Drop => NoWordPosition::Sorted,
+        }
+    }
+}
+
+/// How the input is read.
+struct InputFormat {
+    records: RecordFormat,
+    /// Warn about and skip lines that are not valid UTF-8 instead of failing
+    skip_bad_lines: bool,
+    /// Warn about and skip files that cannot be opened instead of failing
+    ignore_missing: bool,
+    directories: Directories,
+}
+
+/// How the input is split into the records that get sorted.
+enum RecordFormat {
+    Lines,
+    /// CSV records with the given delimiter
+    Csv(u8),
+    /// Blank-line-separated blocks of lines
+    Paragraphs,
+    /// Blocks of lines separated by lines equal to the separator
+    Separated(String),
+}
+
+impl InputFormat {
+    fn from_args(args: &Args) -> io::Result<Self> {
+        Ok(InputFormat {
+            records: RecordFormat::from_args(args)?,
+            skip_bad_lines: args.skip_bad_lines,
+            ignore_missing: args.ignore_missing,
+            directories: args.directories,
+        })
+    }
+
+    /// What goes between two records in the output, each of which ends
+    /// with `\n`: nothing between lines, a blank line between paragraphs,
+    /// or the separator line, so the output is framed like the input.
+    fn record_separator(&self) -> String {
+        match &self.records {
+            RecordFormat::Lines | RecordFormat::Csv(_) => String::new(),
+            RecordFormat::Paragraphs => "\n".to_string(),
+            RecordFormat::Separated(separator) => format!("{}\n", separator),
+        }
+    }
+}
+
+/// Writes `records` as they are, framed by `format.record_separator`.
+fn write_records(
+    out: &mut impl Write,
+    format: &InputFormat,
+    records: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    let separator = format.record_separator();
+    for (i, record) in records.enumerate() {
+        let separator = if i == 0 { "" } else { &separator };
+        writeln!(out, "{}{}", separator, record)?;
+    }
+    Ok(())
+}
+
+impl RecordFormat {
+    fn from_args(args: &Args) -> io::Result<Self> {
+        if args.paragraphs {
+            return Ok(RecordFormat::Paragraphs);
+        }
+        if let Some(separator) = &args.record_sep {
+            return Ok(RecordFormat::Separated(separator.clone()));
+        }
+        if !args.csv {
+            return Ok(RecordFormat::Lines);
+        }
+
+        // The CSV delimiter is `-t` if given, which must then be a single
+        // byte, or a comma
+        match args.field_separator.as_deref().map(str::as_bytes) {
+            None => Ok(RecordFormat::Csv(b',')),
+            Some(&[delimiter]) => Ok(RecordFormat::Csv(delimiter)),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--csv needs a single-byte delimiter for -t",
+            )),
+        }
+    }
+}
+
+/// Parses `--word-class`: a named class or a regex character class.
+fn parse_word_class(s: &str) -> Result<WordClass, String> {
+    match s {
+        "alpha" => Ok(WordClass::Alpha),
+        "alnum" => Ok(WordClass::Alnum),
+        _ => WordClass::pattern(s).map_err(|e| e.to_string()),
+    }
+}
+
+/// Parses `--lines START:END` (1-based, inclusive) into 0-based positions.
+fn parse_line_range(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got '{}'", s))?;
+    let parse = |n: &str, default| match n.trim() {
+        "" => Ok(default),
+        n => n
+            .parse::<usize>()
+            .map_err(|_| format!("invalid line number '{}'", n)),
+    };
+    let (start, end) = (parse(start, 1)?, parse(end, usize::MAX)?);
+
+    if start == 0 {
+        return Err("line numbers start at 1".to_string());
+    }
+    if end < start {
+        return Err(format!("range '{}' ends before it starts", s));
+    }
+    Ok(start - 1..end)
+}
+
+/// The delimiter occurrence `--align-on` lines up.
+#[derive(Clone, Copy, Debug)]
+struct AlignOn {
+    delimiter: char,
+    /// Which occurrence, from 1
+    occurrence: usize,
+}
+
+impl AlignOn {
+    /// The column (in characters) of the occurrence in `text`, if it has one.
+    fn column(&self, text: &str) -> Option<usize> {
+        text.chars()
+            .enumerate()
+            .filter(|&(_, c)| c == self.delimiter)
+            .nth(self.occurrence - 1)
+            .map(|(column, _)| column)
+    }
+}
+
+/// Parses `--align-on CHAR[,N]`.
+fn parse_align_on(s: &str) -> Result<AlignOn, String> {
+    let (delimiter, occurrence) = match s.rsplit_once(',') {
+        // ',' alone, or ',,N', aligns on commas
+        Some((delimiter, n)) if !delimiter.is_empty() => (delimiter, Some(n)),
+        _ => (s, None),
+    };
+    let mut chars = delimiter.chars();
+    let (Some(delimiter), None) = (chars.next(), chars.next()) else {
+        return Err(format!("expected a single character, got '{}'", delimiter));
+    };
+    let occurrence = match occurrence {
+        Some(n) => n
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| format!("invalid occurrence '{}' (counted from 1)", n))?,
+        None => 1,
+    };
+    Ok(AlignOn {
+        delimiter,
+        occurrence,
+    })
+}
+
+/// Parses `--byte-key OFFSET,LEN` into a byte range.
+fn parse_byte_key(s: &str) -> Result<Range<usize>, String> {
+    let (offset, len) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected OFFSET,LEN, got '{}'", s))?;
+    let offset: usize = offset
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid offset '{}'", offset))?;
+    let len: usize = len
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid length '{}'", len))?;
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| format!("byte range '{}' is too large", s))?;
+    Ok(offset..end)
+}
+
+/// How directories given as input files are handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Directories {
+    /// Fail, naming the directory
+    Error,
+    /// Skip it with a warning
+    Skip,
+    /// Read every file below the directory, in name order
+    Recurse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ExcludedPlacement {
+    Drop,
+    Top,
+    Bottom,
+}
+
+/// Exit statuses, compatible with GNU sort: 0 on success and 2 on any error,
+/// including usage errors reported by clap. Status 1 is reserved for a
+/// `--check` that finds disorder.
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_DISORDER: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    logger::init(args.quiet, args.verbose);
+    if args.version {
+        version::print(args.verbose);
+        return ExitCode::from(EXIT_SUCCESS);
+    }
+
+    match run(args) {
+        Ok(status) => ExitCode::from(status),
+        // The reader went away (e.g. `ssort big.txt | head`); not worth reporting
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            eprintln!("ssort: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Returns the exit status, which is `EXIT_SUCCESS` unless `--check` finds
+/// disorder.
+fn run(mut args: Args) -> io::Result<u8> {
+    // Create config for the library
+    let config = SortConfig {
+        ignore_case: args.ignore_case,
+        // -i -n matches case the way Unicode defines for normalized text
+        fold_mode: if args.normalize {
+            FoldMode::Canonical
+        } else {
+            FoldMode::Lowercase
+        },
+        use_entire_line: args.use_entire_line,
+        first_line_key: args.paragraphs || args.record_sep.is_some(),
+        ignore_leading_blanks: args.ignore_leading_blanks,
+        dictionary_order: args.dictionary_order,
+        word_chars: args.word_chars.clone(),
+        word_class: args.word_class.clone().unwrap_or_default(),
+        unicode_words: args.unicode_words,
+        reverse: args.reverse,
+        stable: args.stable,
+        right_align: args.right_align,
+        exclude_no_word: args.exclude_no_word || args.no_word_position == Some(NoWordArg::Drop),
+        no_word_position: args
+            .no_word_position
+            .map_or_else(Default::default, Into::into),
+        filter: args.filter.clone(),
+        word_only: args.word_only,
+        normalize: args.normalize,
+        squeeze_whitespace: args.squeeze_ws,
+        trim_key: args.trim_key,
+        ignore_nonprinting: args.ignore_nonprinting,
+        strip_ansi: args.strip_ansi || args.strip_ansi_output,
+        ascii_key: args.ascii_key,
+        ignore_symbols: args.ignore_symbols,
+        phonetic: args.phonetic.map(Into::into),
+        key_words: args.words as usize,
+        byte_key: args.byte_key.clone(),
+        field_separator: if args.csv {
+            Some(
+                args.field_separator
+                    .clone()
+                    .unwrap_or_else(|| ",".to_string()),
+            )
+        } else {
+            args.field_separator
+                .clone()
+                .or_else(|| args.key_after_last.clone())
+        },
+        field: args.field as usize,
+        last_field: args.last_field || args.key_after_last.is_some(),
+        field_compare: args.field_compare,
+        forward: args.forward,
+        trailing_numbers: args.trailing_numbers,
+        by_extension: args.by_extension,
+        domain: args.domain,
+        algorithm: if args.deterministic {
+            SortAlgorithm::Comparison
+        } else if args.radix {
+            SortAlgorithm::Radix
+        } else if args.intern_keys {
+            SortAlgorithm::Interned
+        } else {
+            SortAlgorithm::Comparison
+        },
+        sort_by: if args.by_length {
+            SortBy::KeyLength
+        } else if args.by_frequency {
+            SortBy::Frequency
+        } else if args.by_line_length {
+            SortBy::LineLength
+        } else if args.by_line_width {
+            SortBy::LineWidth
+        } else if args.by_syllables {
+            SortBy::Syllables
+        } else {
+            SortBy::Suffix
+        },
+        tiebreak: if args.deterministic {
+            Tiebreak::Line
+        } else {
+            args.tiebreak.into()
+        },
+        key_fn: None,
+        syllable_fn: None,
+    };
+    let config = match RecordFormat::from_args(&args)? {
+        RecordFormat::Csv(delimiter) => csv_mode::install(config, delimiter),
+        RecordFormat::Lines | RecordFormat::Paragraphs | RecordFormat::Separated(_) => config,
+    };
+    #[cfg(feature = "scripting")]
+    let config = match &args.key_script {
+        Some(path) => script::install(config, path)?,
+        None => config,
+    };
+    #[cfg(feature = "plugins")]
+    let config = match &args.plugin {
+        Some(path) => plugin::install(config, path)?,
+        None => config,
+    };
+
+    config.validate()?;
+
+    if args.deterministic {
+        // Clap has ruled out key sources that can read the clock (plugins
+        // are sandboxed without one); the config above pins the comparison
+        // sort and whole-line tie-breaks, and output is rendered serially
+        log::debug!("deterministic output: order depends only on input and options");
+    }
+
+    if args.watch && args.output.is_none() && !args.each {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--watch needs --output or --each to know where to write",
+            )
+            .exit();
+    }
+
+    if args.command.is_some() && (args.save_index.is_some() || args.load_index.is_some()) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--save-index and --load-index cannot be used with a subcommand",
+            )
+            .exit();
+    }
+
+    if let Some(list) = &args.files_from {
+        if args.command.is_some() {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--files-from cannot be used with a subcommand",
+                )
+                .exit();
+        }
+        args.files = read_files_from(list)?;
+    }
+    if args.glob || cfg!(windows) {
+        args.files = expand_globs(std::mem::take(&mut args.files));
+    }
+
+    let files = match &args.command {
+        Some(command) => command.files(),
+        None if args.load_index.is_some() => None,
+        None => Some(&args.files[..]),
+    };
+    if files.is_some_and(|files| files.is_empty()) && !args.force_stdin && io::stdin().is_terminal()
+    {
+        // Waiting silently for terminal input looks like a hang to new users
+        eprintln!("ssort: no input files given and stdin is a terminal");
+        eprintln!(
+            "Pass one or more FILEs, pipe text into ssort, or use --force-stdin to type the input (end it with Ctrl-D)."
+        );
+        eprintln!("Try 'ssort --help' for more information.");
+        std::process::exit(EXIT_ERROR.into());
+    }
+
+    if args.check {
+        return check(&args, &config);
+    }
+
+    let run = || {
+        if let Some(command) = &args.command {
+            let format = InputFormat::from_args(&args)?;
+            command.run(&config, &format, args.output.as_deref(), args.write_buffer)
+        } else if args.each {
+            // Sort every file on its own, each into its own output file
+            for filename in &args.files {
+                let path = args.output_template.replace("{}", filename);
+                write_atomically(&path, args.write_buffer, |out| {
+                    sort_files(&args, &config, std::slice::from_ref(filename), out)
+                })?;
+            }
+            Ok(())
+        } else if let Some(path) = &args.output {
+            write_atomically(path, args.write_buffer, |out| {
+                sort_files(&args, &config, &args.files, out)
+            })
+        } else {
+            write_to(None, args.write_buffer, |mut out| {
+                sort_files(&args, &config, &args.files, &mut out)
+            })
+        }
+    };
+
+    if args.watch {
+        let outputs: Vec<String> = if args.each {
+            args.files
+                .iter()
+                .map(|filename| args.output_template.replace("{}", filename))
+                .collect()
+        } else {
+            args.output.iter().cloned().collect()
+        };
+        watch::watch(&args.files, &outputs, run)?;
+    } else {
+        run()?;
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+/// Writes to a temporary file next to `path` and renames it into place once
+/// done, so the output may also be one of the inputs and readers never see a
+/// partially written file. Writes are buffered in `buffer` bytes.
+fn write_atomically(
+    path: &str,
+    buffer: usize,
+    write: impl FnOnce(&mut BufWriter<&mut tempfile::NamedTempFile>) -> io::Result<()>,
+) -> io::Result<()> {
+    let path = Path::new(path);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("'{}': {}", path.display(), e));
+
+    let mut builder = tempfile::Builder::new();
+    // Temporary files are private by default; let the umask decide instead,
+    // like a plain File::create would
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let mut file = builder.tempfile_in(directory).map_err(with_path)?;
+
+    {
+        let mut out = BufWriter::with_capacity(buffer, &mut file);
+        write(&mut out)?;
+        out.flush()?;
+    }
+
+    file.persist(path).map_err(|e| with_path(e.error))?;
+    Ok(())
+}
+
+/// Writes to `path` with `write_atomically`, or to stdout without a path,
+/// through a buffer of `buffer` bytes.
+fn write_to(
+    path: Option<&str>,
+    buffer: usize,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    match path {
+        Some(path) => write_atomically(path, buffer, |out| write(out)),
+        None => {
+            // Stdout alone flushes every line
+            let mut out = BufWriter::with_capacity(buffer, io::stdout().lock());
+            write(&mut out)?;
+            out.flush()
+        }
+    }
+}
+
+/// Reports the first disorder, with its file name and line number as in
+/// `sort -c`, and returns `EXIT_DISORDER` if the input is not sorted.
+fn check(args: &Args, config: &SortConfig) -> io::Result<u8> {
+    let format = InputFormat::from_args(args)?;
+    let mut excluded = Vec::new();
+    let names = input_names(args, &args.files, &format)?;
+    // The input and line number (from 1) of every line that is checked
+    let mut positions = Vec::new();
+    let mut lines = Vec::new();
+    for (input, name) in names.iter().enumerate() {
+        let file_lines = read_input(std::slice::from_ref(name), &format)?;
+        for (number, line) in file_lines.into_iter().enumerate() {
+            if let Some(line) = exclude_line(args, line, &mut excluded) {
+                positions.push((input, number + 1));
+                lines.push(line);
+            }
+        }
+    }
+
+    match config.is_sorted(&lines) {
+        Ok(()) => Ok(EXIT_SUCCESS),
+        Err(disorder) => {
+            let (input, number) = positions[disorder.index];
+            eprintln!(
+                "ssort: {}:{}: disorder: {}",
+                names[input], number, disorder.line
+            );
+            Ok(EXIT_DISORDER)
+        }
+    }
+}
+
+/// Reads, sorts and writes `files` (or stdin if empty) as one input.
+fn sort_files(
+    args: &Args,
+    config: &SortConfig,
+    files: &[String],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    // Read input from files or stdin, and process and sort it using the library
+    let format = InputFormat::from_args(args)?;
+    let record_separator = format.record_separator();
+    let read_start = Instant::now();
+    let mut excluded = Vec::new();
+    // Where each input file's lines end, for set operations and
+    // --with-filename, and the names of the files
+    let mut input_ends = Vec::new();
+    let mut input_names = Vec::new();
+    let sections = match (&args.section_marker, &args.lines) {
+        (Some(marker), _) => {
+            let lines = exclude_lines(args, read_input(files, &format)?, &mut excluded);
+            Some(sections::sort_sections(config, lines, marker))
+        }
+        (None, Some(range)) => {
+            let lines = exclude_lines(args, read_input(files, &format)?, &mut excluded);
+            Some(sections::sort_range(config, lines, range.clone()))
+        }
+        (None, None) if args.group_by_file => {
+            let inputs = read_inputs(args, files, &format, &mut excluded)?;
+            Some(sections::group_by_file(config, inputs))
+        }
+        (None, None) => None,
+    };
+    let (sorted, padding_info, timings) = match (sections, args.max_memory) {
+        (Some(sorted), _) => (
+            Sorted::Sections(sorted.sections),
+            sorted.padding_info,
+            sorted.timings,
+        ),
+        (None, Some(max_memory)) => {
+            let mut sorter = SpillSorter::new(config, max_memory);
+            read_lines(files, &format, |line| {
+                match exclude_line(args, line, &mut excluded) {
+                    Some(line) => sorter.push(line),
+                    None => Ok(()),
+                }
+            })?;
+            sorter.finish()?
+        }
+        (None, None) if let Some(path) = &args.load_index => {
+            let (processed, padding_info) = saved::load(path, config)?;
+            (
+                Sorted::InMemory(processed),
+                padding_info,
+                PhaseTimings::default(),
+            )
+        }
+        (None, None) if args.set_op().is_some() || args.with_filename => {
+            let (names, inputs) = read_inputs(args, files, &format, &mut excluded)?
+                .into_iter()
+                .unzip();
+            input_names = names;
+            let sorted = setops::sort_inputs(config, inputs);
+            input_ends = sorted.input_ends;
+            (
+                Sorted::InMemory(sorted.processed),
+                sorted.padding_info,
+                sorted.timings,
+            )
+        }
+        (None, None) => {
+            let lines = exclude_lines(args, read_input(files, &format)?, &mut excluded);
+            let (processed, padding_info, timings) = config.process_lines_timed(lines);
+            let processed = match &args.save_index {
+                Some(path) => {
+                    saved::save(path, args.write_buffer, config, processed, padding_info)?
+                }
+                None => processed,
+            };
+            (Sorted::InMemory(processed), padding_info, timings)
+        }
+    };
+    // When spilling, chunks are processed while reading, so count that separately
+    let read_time = read_start
+        .elapsed()
+        .saturating_sub(timings.prepare + timings.sort);
+
+    // Write results
+    let write_start = Instant::now();
+    let mut style = OutputStyle {
+        padding_info,
+        word_only: args.word_only,
+        right_align: args.right_align,
+        // Escape sequences mean nothing in HTML
+        strip_ansi: args.strip_ansi_output || args.format != OutputFormat::Text,
+        normalize: args.write_normalized,
+        fold: args.output_folded.then_some(config.fold_mode),
+        right_edge: args.align_right_edge.then(term::width),
+        align_on: None,
+        key_column: None,
+        file_names: None,
+        format: args.format,
+        record_separator: &record_separator,
+        written: AtomicBool::new(false),
+        serial: args.deterministic,
+    };
+    // Like -a, these line up over all lines, not only those written
+    if let Some(align_on) = args.align_on {
+        let column = all_lines(&sorted)
+            .filter_map(|p| align_on.column(&style.text(p)))
+            .max();
+        style.align_on = column.map(|column| (align_on, column));
+    }
+    if args.show_key {
+        let width = all_lines(&sorted).map(|p| p.key.chars().count()).max();
+        style.key_column = Some((width.unwrap_or(0), &args.show_key_separator));
+    }
+    if args.with_filename {
+        style.file_names = Some(FileNames {
+            input_ends: &input_ends,
+            names: &input_names,
+            separator: &args.filename_separator,
+        });
+    }
+    let sorted_count = match &sorted {
+        Sorted::InMemory(processed) => processed.len(),
+        Sorted::Merged(merger) => merger.line_count(),
+        Sorted::Sections(sections) => sections
+            .iter()
+            .map(|section| match section {
+                Section::Sorted(processed) => processed.len(),
+                Section::Verbatim(_) => 0,
+            })
+            .sum(),
+    };
+    html::write_start(out, args.format)?;
+    if args.excluded == ExcludedPlacement::Top {
+        for line in &excluded {
+            write_unsorted(out, &style, line)?;
+        }
+    }
+    let written = match sorted {
+        Sorted::InMemory(processed) => write_output(
+            out,
+            page(
+                args,
+                dedup(
+                    args,
+                    config,
+                    processed.len(),
+                    &input_ends,
+                    processed.into_iter().map(Ok),
+                ),
+            ),
+            &style,
+        )?,
+        Sorted::Merged(merger) => write_output(
+            out,
+            page(args, dedup(args, config, merger.line_count(), &[], merger)),
+            &style,
+        )?,
+        Sorted::Sections(sections) => {
+            let mut written = 0;
+            for section in sections {
+                match section {
+                    Section::Verbatim(line) => write_unsorted(out, &style, &line)?,
+                    Section::Sorted(processed) => {
+                        written += write_output(
+                            out,
+                            dedup(
+                                args,
+                                config,
+                                processed.len(),
+                                &[],
+                                processed.into_iter().map(Ok),
+                            ),
+                            &style,
+                        )?
+                    }
+                }
+            }
+            written
+        }
+    };
+    if args.excluded == ExcludedPlacement::Bottom {
+        for line in &excluded {
+            write_unsorted(out, &style, line)?;
+        }
+    }
+    html::write_end(out, args.format)?;
+    let write_time = write_start.elapsed();
+
+    let phases = [
+        ("read", read_time),
+        ("prepare", timings.prepare),
+        ("sort", timings.sort),
+        ("write", write_time),
+    ];
+    if args.time {
+        report_times(&phases);
+    }
+    if let Some(path) = &args.emit_meta {
+        let meta = meta::Meta {
+            config,
+            files,
+            sorted: sorted_count,
+            written,
+            excluded: excluded.len(),
+            padding_info,
+            timings: &phases,
+        };
+        meta::emit(path.as_deref(), &meta)?;
+    }
+
+    Ok(())
+}
+
+/// All sorted lines, for output options that need to see every line before
+/// writing one. Not available when spilling to disk.
+fn all_lines(sorted: &Sorted) -> Box<dyn Iterator<Item = &ProcessedLine> + '_> {
+    match sorted {
+        Sorted::InMemory(processed) => Box::new(processed.iter()),
+        Sorted::Sections(sections) => Box::new(sections.iter().flat_map(|section| match section {
+            Section::Sorted(processed) => &processed[..],
+            Section::Verbatim(_) => &[],
+        })),
+        Sorted::Merged(_) => unreachable!("conflicts with --max-memory"),
+    }
+}
+
+/// Applies `-u`, `--count`, `--only-duplicates`, `--only-unique` and the set
+/// operations to the `total` sorted lines. `input_ends` are the ends of the
+/// input files for set operations.
+fn dedup<'a>(
+    args: &Args,
+    config: &'a SortConfig,
+    total: usize,
+    input_ends: &'a [usize],
+    sorted: impl Iterator<Item = io::Result<ProcessedLine>> + 'a,
+) -> Box<dyn Iterator<Item = io::Result<ProcessedLine>> + 'a> {
+    let set_op = args.set_op();
+    if !args.unique && !args.count && !args.only_duplicates && !args.only_unique && set_op.is_none()
+    {
+        return Box::new(sorted);
+    }
+
+    let keep = (args.unique || args.count || set_op.is_some()).then_some(args.keep);
+    let (only_duplicates, only_unique) = (args.only_duplicates, args.only_unique);
+    let min_count = args.min_count;
+    let selected = unique::select(
+        config,
+        args.unique_by,
+        keep,
+        move |group| {
+            group.len() >= min_count
+                && match group.len() {
+                    1 => !only_duplicates,
+                    _ => !only_unique,
+                }
+                && set_op.is_none_or(|op| setops::accepts(op, input_ends, group))
+        },
+        sorted,
+    );
+
+    let count_format = args.count.then_some(args.count_format);
+    Box::new(selected.map(move |line| {
+        line.map(|(line, count)| match count_format {
+            Some(format) => unique::with_count(line, count, total, format),
+            None => line,
+        })
+    }))
+}
+
+/// Applies `--skip` and `--take` to the sorted lines.
+fn page<I: Iterator<Item = io::Result<ProcessedLine>>>(
+    args: &Args,
+    sorted: I,
+) -> std::iter::Take<std::iter::Skip<I>> {
+    sorted.skip(args.skip).take(args.take.unwrap_or(usize::MAX))
+}
+
+/// Diverts a line matching `--exclude-pattern` into `excluded` (or drops
+/// it, depending on `--excluded`), returning the line otherwise.
+fn exclude_line(args: &Args, line: String, excluded: &mut Vec<String>) -> Option<String> {
+    match &args.exclude_pattern {
+        Some(pattern) if pattern.is_match(&line) => {
+            if args.excluded != ExcludedPlacement::Drop {
+                excluded.push(line);
             }
+            None
         }
+        _ => Some(line),
+    }
+}
+
+fn exclude_lines(args: &Args, lines: Vec<String>, excluded: &mut Vec<String>) -> Vec<String> {
+    if args.exclude_pattern.is_none() {
+        return lines;
+    }
+    lines
+        .into_iter()
+        .filter_map(|line| exclude_line(args, line, excluded))
+        .collect()
+}
+
+fn report_times(phases: &[(&str, Duration)]) {
+    for (name, duration) in phases {
+        eprintln!("{:<8} {:>12.3?}", format!("{}:", name), duration);
+    }
+    let total: Duration = phases.iter().map(|(_, duration)| *duration).sum();
+    eprintln!("{:<8} {:>12.3?}", "total:", total);
+}
+
+fn read_input(files: &[String], format: &InputFormat) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    read_lines(files, format, |line| {
+        lines.push(line);
+        Ok(())
+    })?;
+    Ok(lines)
+}
+
+/// Reads the inputs that are sorted separately for set operations,
+/// `--with-filename` and `--group-by-file`, along with their names. Set
+/// operations read each file argument as one input; otherwise every file
+/// found in a directory is an input of its own.
+fn read_inputs(
+    args: &Args,
+    files: &[String],
+    format: &InputFormat,
+    excluded: &mut Vec<String>,
+) -> io::Result<Vec<(String, Vec<String>)>> {
+    let mut inputs = Vec::new();
+    for name in input_names(args, files, format)? {
+        let lines = read_input(std::slice::from_ref(&name), format)?;
+        let lines = exclude_lines(args, lines, excluded);
+        let name = match name.as_str() {
+            "-" => "(standard input)".to_string(),
+            _ => name,
+        };
+        inputs.push((name, lines));
+    }
+    Ok(inputs)
+}
+
+/// The inputs `read_inputs` reads, with "-" for stdin.
+fn input_names(args: &Args, files: &[String], format: &InputFormat) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    if files.is_empty() {
+        names.push("-".to_string());
+    }
+    for file in files {
+        if file == "-" || args.set_op().is_some() {
+            names.push(file.clone());
+            continue;
+        }
+        let paths = input_paths(file, format.directories)?;
+        names.extend(paths.iter().map(|path| path.to_string_lossy().into_owned()));
+    }
+    Ok(names)
+}
+
+/// Replaces file names with wildcards by the files they match, in name
+/// order, like a Unix shell. Names that match nothing (or are not valid
+/// patterns) are kept, so opening them reports the problem.
+fn expand_globs(files: Vec<String>) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for file in files {
+        if !file.contains(['*', '?', '[']) {
+            expanded.push(file);
+            continue;
+        }
+
+        let matches: Vec<String> = match glob::glob(&file) {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        log::info!("{} matches {} files", file, matches.len());
+        if matches.is_empty() {
+            expanded.push(file);
+        } else {
+            expanded.extend(matches);
+        }
+    }
+    expanded
+}
+
+/// The input files listed in `list` (or stdin for `-`), one per line.
+/// Empty lines are skipped.
+fn read_files_from(list: &str) -> io::Result<Vec<String>> {
+    let text = if list == "-" {
+        io::read_to_string(io::stdin())?
     } else {
-        for p in processed {
-            writeln!(handle, "{}", p.original)?;
+        std::fs::read_to_string(list)
+            .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", list, e)))?
+    };
+    let files: Vec<String> = text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let invalid = |message: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}': {}", list, message),
+        )
+    };
+    if files.is_empty() {
+        return Err(invalid("no input files listed"));
+    }
+    if list == "-" && files.iter().any(|file| file == "-") {
+        return Err(invalid(
+            "stdin cannot be listed when the list is read from it",
+        ));
+    }
+    log::info!("read {} file names from {}", files.len(), list);
+    Ok(files)
+}
+
+/// Feeds every record (usually a line) of the input files (or stdin) to `f`,
+/// in order.
+fn read_lines(
+    files: &[String],
+    format: &InputFormat,
+    mut f: impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let skip_bad = format.skip_bad_lines;
+    let mut read = |name: &str, reader: &mut dyn BufRead| match &format.records {
+        RecordFormat::Lines => read_source(name, reader, skip_bad, &mut f),
+        RecordFormat::Csv(delimiter) => {
+            csv_mode::read_records(name, reader, *delimiter, skip_bad, &mut f)
+        }
+        RecordFormat::Paragraphs => records::read_records(
+            name,
+            reader,
+            skip_bad,
+            |line| line.trim().is_empty(),
+            &mut f,
+        ),
+        RecordFormat::Separated(separator) => {
+            records::read_records(name, reader, skip_bad, |line| line == separator, &mut f)
+        }
+    };
+
+    if files.is_empty() {
+        // Read from stdin
+        read("stdin", &mut io::stdin().lock())?;
+    } else {
+        // Read from files
+        for filename in files {
+            if filename == "-" {
+                // Read from stdin
+                read("stdin", &mut io::stdin().lock())?;
+                continue;
+            }
+
+            for path in input_paths(filename, format.directories)? {
+                // Read from file
+                let name = path.to_string_lossy();
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) if format.ignore_missing => {
+                        log::warn!("skipping '{}': {}", name, e);
+                        continue;
+                    }
+                    Err(e) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("'{}': {}", name, e),
+                        ));
+                    }
+                };
+                read(&name, &mut BufReader::new(file))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The files to read for the input file `filename`: itself, or for a
+/// directory whatever `directories` says.
+fn input_paths(filename: &str, directories: Directories) -> io::Result<Vec<PathBuf>> {
+    let path = PathBuf::from(filename);
+    // Files that cannot be opened are reported when opening them
+    if !std::fs::metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+        return Ok(vec![path]);
+    }
+
+    match directories {
+        Directories::Error => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "'{}': is a directory (use --directories skip or recurse)",
+                filename
+            ),
+        )),
+        Directories::Skip => {
+            log::warn!("skipping directory '{}'", filename);
+            Ok(Vec::new())
+        }
+        Directories::Recurse => {
+            let mut paths = Vec::new();
+            collect_files(&path, &mut paths)
+                .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", filename, e)))?;
+            log::info!("found {} files in {}", paths.len(), filename);
+            Ok(paths)
         }
     }
+}
+
+/// Adds the files below `directory` to `paths`, sorted by name. Symbolic
+/// links to directories are not followed, so links cannot form a cycle.
+fn collect_files(directory: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&path, paths)?;
+        } else if file_type.is_file() || path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
 
+fn read_source(
+    name: &str,
+    reader: impl BufRead,
+    skip_bad: bool,
+    f: &mut impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut count = 0;
+    for_each_line(name, reader, skip_bad, |line| {
+        count += 1;
+        f(line)
+    })?;
+    log::info!("read {} lines from {}", count, name);
+    Ok(())
+}
+
+/// Feeds the lines of `reader` to `f` like `BufRead::lines`, but read errors
+/// name the source and line. Lines that are not valid UTF-8 are an error,
+/// or with `skip_bad` a warning and skipped.
+fn for_each_line(
+    name: &str,
+    mut reader: impl BufRead,
+    skip_bad: bool,
+    mut f: impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    let mut number = 0;
+    let mut skipped = 0;
+    loop {
+        number += 1;
+        match suffixsort::read_line(&mut reader, &mut buf, number) {
+            Ok(Some(line)) => f(line)?,
+            Ok(None) => break,
+            Err(e @ suffixsort::Error::InvalidUtf8 { .. }) if skip_bad => {
+                log::warn!("'{}': {}, skipping", name, e);
+                skipped += 1;
+            }
+            Err(e @ suffixsort::Error::InvalidUtf8 { .. }) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "'{}': {} (use --skip-bad-lines to skip such lines)",
+                        name, e
+                    ),
+                ));
+            }
+            Err(e) => {
+                let e = io::Error::from(e);
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("'{}': line {}: {}", name, number, e),
+                ));
+            }
+        }
+    }
+    if skipped > 0 {
+        log::info!("skipped {} bad lines in {}", skipped, name);
+    }
     Ok(())
 }
+
+/// Merges the padding of separately processed batches, so they align with
+/// each other.
+fn combine_padding(a: Option<PaddingInfo>, b: Option<PaddingInfo>) -> Option<PaddingInfo> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(PaddingInfo {
+            max_value: a.max_value.max(b.max_value),
+            use_end_pos: b.use_end_pos,
+            key_width: a.key_width.max(b.key_width),
+        }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// How `write_output` writes sorted lines.
+struct OutputStyle<'a> {
+    padding_info: Option<PaddingInfo>,
+    word_only: bool,
+    right_align: bool,
+    strip_ansi: bool,
+    normalize: bool,
+    fold: Option<FoldMode>,
+    /// The column `--align-right-edge` aligns line ends to
+    right_edge: Option<usize>,
+    /// The delimiter `--align-on` lines up, and the column it goes to
+    align_on: Option<(AlignOn, usize)>,
+    /// The width of the `--show-key` column and what follows it
+    key_column: Option<(usize, &'a str)>,
+    file_names: Option<FileNames<'a>>,
+    format: OutputFormat,
+    /// What goes between records; see `InputFormat::record_separator`
+    record_separator: &'a str,
+    /// Whether a record has been written, so the next one needs the separator
+    written: AtomicBool,
+    /// Render on the calling thread only (`--deterministic`)
+    serial: bool,
+}
+
+/// The input files for `--with-filename`, to find which one a line came
+/// from by its index.
+struct FileNames<'a> {
+    input_ends: &'a [usize],
+    names: &'a [String],
+    separator: &'a str,
+}
+
+impl FileNames<'_> {
+    fn name(&self, p: &ProcessedLine) -> &str {
+        &self.names[self.input_ends.partition_point(|&end| end <= p.index)]
+    }
+}
+
+impl OutputStyle<'_> {
+    /// The separator to write before the next record: none before the first.
+    fn next_separator(&self) -> &str {
+        if self.written.swap(true, atomic::Ordering::Relaxed) {
+            self.record_separator
+        } else {
+            ""
+        }
+    }
+
+    /// `line` without escape sequences (`--strip-ansi-output`), in NFC form
+    /// (`--write-normalized`) and case-folded (`--output-folded`), as
+    /// requested.
+    fn plain<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        let line = if self.strip_ansi {
+            suffixsort::strip_ansi(line)
+        } else {
+            Cow::Borrowed(line)
+        };
+        let line = if self.normalize && is_nfc_quick(line.chars()) != IsNormalized::Yes {
+            Cow::Owned(line.nfc().collect())
+        } else {
+            line
+        };
+        match self.fold {
+            Some(mode) => Cow::Owned(suffixsort::fold_case(&line, mode)),
+            None => line,
+        }
+    }
+
+    /// What is written for `p`, before padding.
+    fn text<'p>(&self, p: &'p ProcessedLine) -> Cow<'p, str> {
+        if self.word_only {
+            // Output only the word used for sorting
+            Cow::Borrowed(p.key.as_str())
+        } else {
+            self.plain(&p.original)
+        }
+    }
+
+    /// The number of spaces that go before `text`, the output for `p`.
+    fn padding(&self, p: &ProcessedLine, text: &str) -> usize {
+        if let Some(edge) = self.right_edge {
+            // Escape sequences take up no columns
+            return edge.saturating_sub(suffixsort::strip_ansi(text).width());
+        }
+        if let Some((align_on, column)) = self.align_on {
+            return align_on
+                .column(text)
+                .map_or(0, |own| column.saturating_sub(own));
+        }
+        let Some(padding_info) = self.padding_info else {
+            return 0;
+        };
+
+        if self.word_only {
+            if self.right_align {
+                padding_info.key_width.saturating_sub(p.key.chars().count())
+            } else {
+                0
+            }
+        } else if padding_info.use_end_pos {
+            // Line up where the key ends in the line, in any key mode that
+            // knows the key's position (see `compute_padding_info`)
+            match (p.visual_start, p.word_length) {
+                (Some(visual_start), Some(word_length)) => padding_info
+                    .max_value
+                    .saturating_sub(visual_start + word_length),
+                // Line has no word, output without padding
+                _ => 0,
+            }
+        } else {
+            // Custom keys do not say where they are in the line; pad by length
+            padding_info.max_value.saturating_sub(p.key.chars().count())
+        }
+    }
+}
+
+/// Writes a line that was passed through unsorted.
+fn write_unsorted(handle: &mut impl Write, style: &OutputStyle, line: &str) -> io::Result<()> {
+    let line = style.plain(line);
+    match style.format {
+        OutputFormat::Text => writeln!(handle, "{}{}", style.next_separator(), line),
+        format => html::write_line(handle, format, None, "", &line),
+    }
+}
+
+/// Lines are rendered in batches of this many, split across threads in
+/// chunks of `RENDER_CHUNK` lines, so padding and formatting large outputs
+/// does not run on one core.
+const RENDER_BATCH: usize = 64 * 1024;
+const RENDER_CHUNK: usize = 4096;
+
+/// Writes the sorted lines, returning how many were written. An error from
+/// `processed` is returned after the lines before it are written.
+fn write_output(
+    handle: &mut impl Write,
+    mut processed: impl Iterator<Item = io::Result<ProcessedLine>>,
+    style: &OutputStyle,
+) -> io::Result<usize> {
+    let mut written = 0;
+    let mut batch = Vec::with_capacity(RENDER_BATCH);
+    loop {
+        let mut error = None;
+        for p in processed.by_ref().take(RENDER_BATCH) {
+            match p {
+                Ok(p) => batch.push(p),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        if batch.is_empty() && error.is_none() {
+            return Ok(written);
+        }
+
+        // Chunks are rendered independently, then written in order
+        let render = |chunk: &[ProcessedLine]| {
+            let mut buffer = Vec::new();
+            for p in chunk {
+                render_line(&mut buffer, p, style)?;
+            }
+            Ok(buffer)
+        };
+        let buffers = if style.serial {
+            batch
+                .chunks(RENDER_CHUNK)
+                .map(render)
+                .collect::<io::Result<Vec<Vec<u8>>>>()?
+        } else {
+            batch
+                .par_chunks(RENDER_CHUNK)
+                .map(render)
+                .collect::<io::Result<Vec<Vec<u8>>>>()?
+        };
+        for buffer in buffers {
+            // Text records are rendered after a separator, which the first
+            // record written goes without
+            let skip = match style.format {
+                OutputFormat::Text => style.record_separator.len() - style.next_separator().len(),
+                _ => 0,
+            };
+            handle.write_all(&buffer[skip..])?;
+        }
+        written += batch.len();
+        batch.clear();
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+    }
+}
+
+/// Formats one sorted line as it is written.
+fn render_line(out: &mut impl Write, p: &ProcessedLine, style: &OutputStyle) -> io::Result<()> {
+    let text = style.text(p);
+    if style.format != OutputFormat::Text {
+        let prefix = match &style.file_names {
+            Some(files) => format!("{}{}", files.name(p), files.separator),
+            None => String::new(),
+        };
+        return html::write_line(out, style.format, Some(p), &prefix, &text);
+    }
+    // `write_output` drops this again before the first record written
+    out.write_all(style.record_separator.as_bytes())?;
+    if let Some(files) = &style.file_names {
+        write!(out, "{}{}", files.name(p), files.separator)?;
+    }
+    if let Some((width, separator)) = style.key_column {
+        write!(out, "{:>width$}{}", p.key, separator, width = width)?;
+    }
+    let padding = style.padding(p, &text);
+    writeln!(out, "{:padding$}{}", "", text, padding = padding)
+}
@@ -0,0 +1,42 @@
+//! `ssort merge`: merge inputs that are already sorted.
+
+use crate::InputFormat;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use suffixsort::SortConfig;
+
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// also merge FILE.delta, written by `insert --delta`, for every FILE that has one
+    #[arg(long = "delta")]
+    delta: bool,
+
+    /// sorted input files (use '-' for stdin, default if no files provided)
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Writes the lines of all inputs, each sorted with the same options, in
+/// sorted order without sorting them again. Lines with equal keys keep the
+/// order of the inputs.
+pub fn run(
+    config: &SortConfig,
+    format: &InputFormat,
+    args: &MergeArgs,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut inputs = Vec::new();
+    for file in &args.files {
+        inputs.push(crate::read_input(std::slice::from_ref(file), format)?);
+        let delta = crate::insert::delta_path(file);
+        if args.delta && Path::new(&delta).exists() {
+            inputs.push(crate::read_input(&[delta], format)?);
+        }
+    }
+    if args.files.is_empty() {
+        inputs.push(crate::read_input(&[], format)?);
+    }
+
+    crate::write_records(out, format, config.merge(inputs))
+}
@@ -0,0 +1,173 @@
+//! `--emit-meta`: a JSON record of how the output was produced, for
+//! pipelines that keep track of their inputs and settings.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+use suffixsort::{PaddingInfo, SortConfig, WordClass};
+
+/// What `--emit-meta` reports about one run.
+pub struct Meta<'a> {
+    pub config: &'a SortConfig,
+    pub files: &'a [String],
+    /// Lines that were sorted, after filtering
+    pub sorted: usize,
+    /// Sorted lines written, after -u, --skip, --take and the like
+    pub written: usize,
+    /// Lines matching --exclude-pattern
+    pub excluded: usize,
+    pub padding_info: Option<PaddingInfo>,
+    pub timings: &'a [(&'a str, Duration)],
+}
+
+/// Writes `meta` as one line of JSON to `path`, or to stderr without one.
+pub fn emit(path: Option<&str>, meta: &Meta) -> io::Result<()> {
+    let json = to_json(meta);
+    match path {
+        Some(path) => File::create(path)
+            .and_then(|mut file| writeln!(file, "{}", json))
+            .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", path, e))),
+        None => writeln!(io::stderr(), "{}", json),
+    }
+}
+
+fn to_json(meta: &Meta) -> String {
+    let config = meta.config;
+    let mut json = String::from("{");
+    let _ = write!(json, "\"version\":{}", string(env!("CARGO_PKG_VERSION")));
+
+    json.push_str(",\"config\":{");
+    let fields = [
+        ("ignore_case", config.ignore_case.to_string()),
+        ("fold_mode", string(&format!("{:?}", config.fold_mode))),
+        ("use_entire_line", config.use_entire_line.to_string()),
+        ("first_line_key", config.first_line_key.to_string()),
+        (
+            "ignore_leading_blanks",
+            config.ignore_leading_blanks.to_string(),
+        ),
+        ("dictionary_order", config.dictionary_order.to_string()),
+        ("word_chars", string(&config.word_chars)),
+        ("word_class", word_class(&config.word_class)),
+        ("unicode_words", config.unicode_words.to_string()),
+        ("reverse", config.reverse.to_string()),
+        ("stable", config.stable.to_string()),
+        ("right_align", config.right_align.to_string()),
+        ("exclude_no_word", config.exclude_no_word.to_string()),
+        (
+            "no_word_position",
+            string(&format!("{:?}", config.no_word_position)),
+        ),
+        (
+            "filter",
+            config
+                .filter
+                .as_ref()
+                .map_or("null".to_string(), |f| string(f.as_str())),
+        ),
+        ("word_only", config.word_only.to_string()),
+        ("normalize", config.normalize.to_string()),
+        ("squeeze_whitespace", config.squeeze_whitespace.to_string()),
+        ("trim_key", config.trim_key.to_string()),
+        ("ignore_nonprinting", config.ignore_nonprinting.to_string()),
+        ("strip_ansi", config.strip_ansi.to_string()),
+        ("ascii_key", config.ascii_key.to_string()),
+        ("ignore_symbols", config.ignore_symbols.to_string()),
+        (
+            "phonetic",
+            config
+                .phonetic
+                .map_or("null".to_string(), |p| string(&format!("{:?}", p))),
+        ),
+        ("key_words", config.key_words.to_string()),
+        (
+            "byte_key",
+            config
+                .byte_key
+                .as_ref()
+                .map_or("null".to_string(), |r| format!("[{},{}]", r.start, r.end)),
+        ),
+        (
+            "field_separator",
+            config
+                .field_separator
+                .as_deref()
+                .map_or("null".to_string(), string),
+        ),
+        ("field", config.field.to_string()),
+        ("last_field", config.last_field.to_string()),
+        ("field_compare", config.field_compare.to_string()),
+        ("forward", config.forward.to_string()),
+        ("trailing_numbers", config.trailing_numbers.to_string()),
+        ("by_extension", config.by_extension.to_string()),
+        ("domain", config.domain.to_string()),
+        ("algorithm", string(&format!("{:?}", config.algorithm))),
+        ("sort_by", string(&format!("{:?}", config.sort_by))),
+        ("tiebreak", string(&format!("{:?}", config.tiebreak))),
+        ("custom_key", config.key_fn.is_some().to_string()),
+    ];
+    for (i, (name, value)) in fields.iter().enumerate() {
+        let comma = if i == 0 { "" } else { "," };
+        let _ = write!(json, "{}{}:{}", comma, string(name), value);
+    }
+    json.push('}');
+
+    let files: Vec<String> = meta.files.iter().map(|file| string(file)).collect();
+    let _ = write!(json, ",\"files\":[{}]", files.join(","));
+    let _ = write!(
+        json,
+        ",\"lines\":{{\"sorted\":{},\"written\":{},\"excluded\":{}}}",
+        meta.sorted, meta.written, meta.excluded
+    );
+    match meta.padding_info {
+        Some(padding) => {
+            let _ = write!(
+                json,
+                ",\"padding\":{{\"max_value\":{},\"use_end_pos\":{},\"key_width\":{}}}",
+                padding.max_value, padding.use_end_pos, padding.key_width
+            );
+        }
+        None => json.push_str(",\"padding\":null"),
+    }
+
+    let timings: Vec<String> = meta
+        .timings
+        .iter()
+        .map(|(phase, duration)| {
+            format!("{}:{:.3}", string(phase), duration.as_secs_f64() * 1000.0)
+        })
+        .collect();
+    let _ = write!(json, ",\"milliseconds\":{{{}}}", timings.join(","));
+    json.push('}');
+    json
+}
+
+fn word_class(class: &WordClass) -> String {
+    match class {
+        WordClass::Alpha => string("alpha"),
+        WordClass::Alnum => string("alnum"),
+        WordClass::Pattern(pattern) => string(pattern.as_str()),
+    }
+}
+
+/// `s` as a JSON string literal.
+fn string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,180 @@
+//! `--plugin`: derive keys with a sandboxed WebAssembly module.
+//!
+//! A plugin is a WASI (or plain wasm32) module exporting:
+//!
+//! - `memory`: its linear memory
+//! - `alloc(len: i32) -> i32`: returns a buffer of `len` bytes for the host
+//!   to write the line into
+//! - `extract_key(ptr: i32, len: i32) -> i64`: derives the key from the UTF-8
+//!   line at `ptr..ptr + len` and returns `(key_ptr << 32) | key_len`
+//! - optionally `dealloc(ptr: i32, len: i32)`: called for the line buffer and
+//!   the key once the host is done with them
+//!
+//! Plugins get no capabilities: every WASI import except `proc_exit` is
+//! stubbed out and fails with `ENOSYS`.
+
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use suffixsort::SortConfig;
+use wasmi::core::ValType;
+use wasmi::{Engine, ExternType, Instance, Linker, Memory, Module, Store, TypedFunc, Val};
+
+const WASI_MODULE: &str = "wasi_snapshot_preview1";
+const ENOSYS: i32 = 52;
+
+struct Plugin {
+    engine: Engine,
+    module: Module,
+    linker: Linker<()>,
+    /// Instances are not thread-safe, so each sorting thread borrows one
+    idle: Mutex<Vec<PluginInstance>>,
+}
+
+struct PluginInstance {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: Option<TypedFunc<(i32, i32), ()>>,
+    extract_key: TypedFunc<(i32, i32), i64>,
+}
+
+/// Loads the plugin at `path` and installs it as the key function of
+/// `config`. Loading fails if the module is invalid or lacks the exports
+/// described in the module documentation.
+pub fn install(config: SortConfig, path: &str) -> io::Result<SortConfig> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("'{}': {}", path, e))
+    };
+
+    let bytes =
+        fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", path, e)))?;
+    let engine = Engine::default();
+    let module = Module::new(&engine, &bytes[..]).map_err(|e| invalid(&e))?;
+    let linker = sandbox_linker(&engine, &module).map_err(|e| invalid(&e))?;
+
+    let plugin = Arc::new(Plugin {
+        engine,
+        module,
+        linker,
+        idle: Mutex::new(Vec::new()),
+    });
+
+    // Instantiate once up front so a broken plugin fails before sorting
+    let instance = plugin.instantiate().map_err(|e| invalid(&e))?;
+    plugin.idle.lock().unwrap().push(instance);
+
+    Ok(config.with_key_fn(move |line| Cow::Owned(plugin.extract_key(line))))
+}
+
+/// Defines every WASI function the module imports as a stub, so modules built
+/// for `wasm32-wasip1` can be instantiated without granting any access.
+fn sandbox_linker(engine: &Engine, module: &Module) -> Result<Linker<()>, wasmi::Error> {
+    let mut linker = Linker::new(engine);
+
+    for import in module.imports() {
+        let (ExternType::Func(ty), WASI_MODULE) = (import.ty(), import.module()) else {
+            continue;
+        };
+
+        if import.name() == "proc_exit" {
+            linker.func_new(WASI_MODULE, import.name(), ty.clone(), |_, params, _| {
+                let status = match params.first() {
+                    Some(Val::I32(status)) => *status,
+                    _ => 1,
+                };
+                Err(wasmi::Error::i32_exit(status))
+            })?;
+        } else {
+            linker.func_new(WASI_MODULE, import.name(), ty.clone(), |_, _, results| {
+                for result in results.iter_mut() {
+                    *result = match result.ty() {
+                        ValType::I32 => Val::I32(ENOSYS),
+                        ty => Val::default(ty),
+                    };
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(linker)
+}
+
+impl Plugin {
+    fn instantiate(&self) -> Result<PluginInstance, wasmi::Error> {
+        let mut store = Store::new(&self.engine, ());
+        let instance: Instance = self
+            .linker
+            .instantiate(&mut store, &self.module)?
+            .start(&mut store)?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| wasmi::Error::new("plugin does not export `memory`"))?;
+        let alloc = instance.get_typed_func(&store, "alloc")?;
+        let extract_key = instance.get_typed_func(&store, "extract_key")?;
+        let dealloc = instance.get_typed_func(&store, "dealloc").ok();
+
+        Ok(PluginInstance {
+            store,
+            memory,
+            alloc,
+            dealloc,
+            extract_key,
+        })
+    }
+
+    /// Runs the plugin on `line`. Failures are reported as warnings and give
+    /// the line an empty key, like a line without a word.
+    fn extract_key(&self, line: &str) -> String {
+        let instance = self.idle.lock().unwrap().pop();
+        let mut instance = match instance.map_or_else(|| self.instantiate(), Ok) {
+            Ok(instance) => instance,
+            Err(e) => {
+                log::warn!("plugin failed to start: {}", e);
+                return String::new();
+            }
+        };
+
+        match instance.call(line) {
+            Ok(key) => {
+                self.idle.lock().unwrap().push(instance);
+                key
+            }
+            Err(e) => {
+                // The instance may be in a bad state after a trap; drop it
+                log::warn!("plugin failed on '{}': {}", line, e);
+                String::new()
+            }
+        }
+    }
+}
+
+impl PluginInstance {
+    fn call(&mut self, line: &str) -> Result<String, wasmi::Error> {
+        let too_long = || wasmi::Error::new("line is too long for the plugin");
+        let len = i32::try_from(line.len()).map_err(|_| too_long())?;
+
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, line.as_bytes())
+            .map_err(|e| wasmi::Error::new(e.to_string()))?;
+
+        let packed = self.extract_key.call(&mut self.store, (ptr, len))? as u64;
+        let (key_ptr, key_len) = ((packed >> 32) as u32, packed as u32);
+
+        let mut key = vec![0; key_len as usize];
+        self.memory
+            .read(&self.store, key_ptr as usize, &mut key)
+            .map_err(|e| wasmi::Error::new(e.to_string()))?;
+
+        if let Some(dealloc) = &self.dealloc {
+            dealloc.call(&mut self.store, (ptr, len))?;
+            dealloc.call(&mut self.store, (key_ptr as i32, key_len as i32))?;
+        }
+
+        Ok(String::from_utf8_lossy(&key).into_owned())
+    }
+}
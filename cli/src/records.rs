@@ -0,0 +1,44 @@
+//! Multi-line records: `--paragraphs` and `--record-sep`.
+
+use std::io;
+use std::io::BufRead;
+
+/// Feeds every block of lines between separator lines in `reader` to `f`,
+/// with its lines joined by `\n`. Runs of separators count as one. Lines
+/// that are not valid UTF-8 are skipped with `skip_bad`, an error otherwise.
+pub fn read_records(
+    name: &str,
+    reader: impl BufRead,
+    skip_bad: bool,
+    is_separator: impl Fn(&str) -> bool,
+    f: &mut impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut count = 0;
+    let mut block = String::new();
+    // Whether `block` has a line, which may be empty
+    let mut started = false;
+
+    crate::for_each_line(name, reader, skip_bad, |line| {
+        if is_separator(&line) {
+            if started {
+                f(std::mem::take(&mut block))?;
+                started = false;
+                count += 1;
+            }
+        } else {
+            if started {
+                block.push('\n');
+            }
+            block.push_str(&line);
+            started = true;
+        }
+        Ok(())
+    })?;
+    if started {
+        f(block)?;
+        count += 1;
+    }
+
+    log::info!("read {} records from {}", count, name);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `--save-index` and `--load-index`: keep sorted lines on disk so a large
+//! static input can be queried again without sorting it again.
+
+use std::fs::File;
+use std::io;
+use suffixsort::index::SavedSort;
+use suffixsort::{PaddingInfo, ProcessedLine, SortConfig};
+
+/// Bumped when the options `options` describes change.
+const OPTIONS_VERSION: u32 = 2;
+
+/// Describes the options that decide which lines are kept, their keys and
+/// their order, so loading lines sorted with different ones is caught.
+/// Options that only change the output or how the sort runs (`algorithm`,
+/// `right_align`, `word_only`) are left out. Scripts and plugins are not
+/// told apart.
+fn options(config: &SortConfig) -> String {
+    let fields = [
+        ("ignore_case", format!("{:?}", config.ignore_case)),
+        ("fold_mode", format!("{:?}", config.fold_mode)),
+        ("use_entire_line", format!("{:?}", config.use_entire_line)),
+        ("first_line_key", format!("{:?}", config.first_line_key)),
+        (
+            "ignore_leading_blanks",
+            format!("{:?}", config.ignore_leading_blanks),
+        ),
+        ("dictionary_order", format!("{:?}", config.dictionary_order)),
+        ("word_chars", format!("{:?}", config.word_chars)),
+        ("word_class", format!("{:?}", config.word_class)),
+        ("unicode_words", format!("{:?}", config.unicode_words)),
+        ("reverse", format!("{:?}", config.reverse)),
+        ("stable", format!("{:?}", config.stable)),
+        ("exclude_no_word", format!("{:?}", config.exclude_no_word)),
+        (
+            "filter",
+            format!("{:?}", config.filter.as_ref().map(|f| f.as_str())),
+        ),
+        ("no_word_position", format!("{:?}", config.no_word_position)),
+        ("normalize", format!("{:?}", config.normalize)),
+        (
+            "squeeze_whitespace",
+            format!("{:?}", config.squeeze_whitespace),
+        ),
+        ("trim_key", format!("{:?}", config.trim_key)),
+        (
+            "ignore_nonprinting",
+            format!("{:?}", config.ignore_nonprinting),
+        ),
+        ("strip_ansi", format!("{:?}", config.strip_ansi)),
+        ("ascii_key", format!("{:?}", config.ascii_key)),
+        ("ignore_symbols", format!("{:?}", config.ignore_symbols)),
+        ("phonetic", format!("{:?}", config.phonetic)),
+        ("key_words", format!("{:?}", config.key_words)),
+        ("byte_key", format!("{:?}", config.byte_key)),
+        ("field_separator", format!("{:?}", config.field_separator)),
+        ("field", format!("{:?}", config.field)),
+        ("last_field", format!("{:?}", config.last_field)),
+        ("field_compare", format!("{:?}", config.field_compare)),
+        ("forward", format!("{:?}", config.forward)),
+        ("trailing_numbers", format!("{:?}", config.trailing_numbers)),
+        ("by_extension", format!("{:?}", config.by_extension)),
+        ("domain", format!("{:?}", config.domain)),
+        ("sort_by", format!("{:?}", config.sort_by)),
+        ("tiebreak", format!("{:?}", config.tiebreak)),
+        ("custom_key", format!("{:?}", config.key_fn.is_some())),
+        (
+            "custom_syllables",
+            format!("{:?}", config.syllable_fn.is_some()),
+        ),
+    ];
+
+    let mut options = format!("v{}", OPTIONS_VERSION);
+    for (name, value) in fields {
+        options.push_str(&format!(";{}={}", name, value));
+    }
+    options
+}
+
+/// Writes the sorted lines to `path` through a buffer of `buffer` bytes,
+/// handing them back afterwards.
+pub fn save(
+    path: &str,
+    buffer: usize,
+    config: &SortConfig,
+    lines: Vec<ProcessedLine>,
+    padding_info: Option<PaddingInfo>,
+) -> io::Result<Vec<ProcessedLine>> {
+    let saved = SavedSort {
+        lines,
+        padding_info,
+        options: options(config),
+    };
+    crate::write_atomically(path, buffer, |out| {
+        saved
+            .write_to(out)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    })?;
+    log::info!("saved {} sorted lines to {}", saved.lines.len(), path);
+    Ok(saved.lines)
+}
+
+/// Loads the sorted lines saved in `path`, which must have been sorted with
+/// the same options as `config`.
+pub fn load(
+    path: &str,
+    config: &SortConfig,
+) -> io::Result<(Vec<ProcessedLine>, Option<PaddingInfo>)> {
+    let with_path =
+        |kind, e: &dyn std::fmt::Display| io::Error::new(kind, format!("'{}': {}", path, e));
+
+    let file = File::open(path).map_err(|e| with_path(e.kind(), &e))?;
+    let saved =
+        SavedSort::read_from(file).map_err(|e| with_path(io::ErrorKind::InvalidData, &e))?;
+    if saved.options != options(config) {
+        return Err(with_path(
+            io::ErrorKind::InvalidInput,
+            &"saved with different sort options",
+        ));
+    }
+    let lines = saved.lines;
+    log::info!("loaded {} sorted lines from {}", lines.len(), path);
+
+    // The saved padding is for the options the lines were saved with
+    let padding_info = config
+        .right_align
+        .then(|| config.compute_padding_info(&lines));
+    Ok((lines, padding_info))
+}
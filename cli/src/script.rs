@@ -0,0 +1,42 @@
+//! `--key-script`: derive keys with a user-supplied rhai script.
+
+use rhai::{AST, Engine, Scope};
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use suffixsort::SortConfig;
+
+/// Compiles the script at `path` and installs it as the key function of
+/// `config`.
+///
+/// The script sees the current line as the variable `line` and its last
+/// expression becomes the key, e.g. `line.split(",")[1]`. Syntax errors are
+/// reported up front; a line whose evaluation fails gets an empty key and a
+/// warning.
+pub fn install(config: SortConfig, path: &str) -> io::Result<SortConfig> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", path, e)))?;
+
+    let engine = Engine::new();
+    let ast = engine
+        .compile(&source)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}': {}", path, e)))?;
+
+    let script = Arc::new((engine, ast));
+    Ok(config.with_key_fn(move |line| Cow::Owned(evaluate(&script, line))))
+}
+
+fn evaluate((engine, ast): &(Engine, AST), line: &str) -> String {
+    let mut scope = Scope::new();
+    scope.push("line", line.to_string());
+
+    match engine.eval_ast_with_scope::<rhai::Dynamic>(&mut scope, ast) {
+        Ok(key) if key.is_unit() => String::new(),
+        Ok(key) => key.to_string(),
+        Err(e) => {
+            log::warn!("key script failed on '{}': {}", line, e);
+            String::new()
+        }
+    }
+}
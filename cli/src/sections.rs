@@ -0,0 +1,109 @@
+//! `--section-marker` and `--lines`: sort parts of the input, leaving the
+//! other lines in place. `--group-by-file`: sort each input file on its own.
+
+use regex::Regex;
+use std::ops::Range;
+use suffixsort::{PaddingInfo, PhaseTimings, ProcessedLine, SortConfig};
+
+/// A stretch of output: a line written as is, or sorted lines.
+pub enum Section {
+    Verbatim(String),
+    Sorted(Vec<ProcessedLine>),
+}
+
+/// The sorted sections along with the padding over all of them, so
+/// alignment is consistent across the whole output.
+pub struct SortedSections {
+    pub sections: Vec<Section>,
+    pub padding_info: Option<PaddingInfo>,
+    pub timings: PhaseTimings,
+}
+
+/// Sorts each run of lines between lines matching `marker` on its own.
+pub fn sort_sections(config: &SortConfig, lines: Vec<String>, marker: &Regex) -> SortedSections {
+    let mut sorted = SortedSections {
+        sections: Vec::new(),
+        padding_info: None,
+        timings: PhaseTimings::default(),
+    };
+
+    let mut pending = Vec::new();
+    for line in lines {
+        if marker.is_match(&line) {
+            sorted.push_sorted(config, std::mem::take(&mut pending));
+            sorted.sections.push(Section::Verbatim(line));
+        } else {
+            pending.push(line);
+        }
+    }
+    sorted.push_sorted(config, pending);
+
+    log::info!(
+        "sorted {} sections",
+        sorted
+            .sections
+            .iter()
+            .filter(|s| matches!(s, Section::Sorted(_)))
+            .count()
+    );
+    sorted
+}
+
+/// Sorts the lines at positions `range` (0-based) and passes the lines before
+/// and after through unchanged.
+pub fn sort_range(config: &SortConfig, lines: Vec<String>, range: Range<usize>) -> SortedSections {
+    let mut sorted = SortedSections {
+        sections: Vec::new(),
+        padding_info: None,
+        timings: PhaseTimings::default(),
+    };
+
+    let mut lines = lines.into_iter();
+    let before = range.start.min(lines.len());
+    sorted
+        .sections
+        .extend(lines.by_ref().take(before).map(Section::Verbatim));
+    let selected = lines.by_ref().take(range.len()).collect();
+    sorted.push_sorted(config, selected);
+    sorted.sections.extend(lines.map(Section::Verbatim));
+
+    sorted
+}
+
+/// Sorts the lines of each named input on its own, each after a header
+/// like `==> name <==` as written by `head` and `tail`, with an empty line
+/// between inputs.
+pub fn group_by_file(config: &SortConfig, inputs: Vec<(String, Vec<String>)>) -> SortedSections {
+    let mut sorted = SortedSections {
+        sections: Vec::new(),
+        padding_info: None,
+        timings: PhaseTimings::default(),
+    };
+
+    for (i, (name, lines)) in inputs.into_iter().enumerate() {
+        if i > 0 {
+            sorted.sections.push(Section::Verbatim(String::new()));
+        }
+        sorted
+            .sections
+            .push(Section::Verbatim(format!("==> {} <==", name)));
+        sorted.push_sorted(config, lines);
+    }
+
+    sorted
+}
+
+impl SortedSections {
+    fn push_sorted(&mut self, config: &SortConfig, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let (processed, padding_info, timings) = config.process_lines_timed(lines);
+        self.timings.prepare += timings.prepare;
+        self.timings.sort += timings.sort;
+        self.padding_info = crate::combine_padding(self.padding_info, padding_info);
+
+        self.sections.push(Section::Sorted(processed));
+    }
+}
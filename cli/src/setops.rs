@@ -0,0 +1,59 @@
+//! `--union`, `--intersect` and `--difference`: treat each input file as a
+//! set of keys.
+
+use suffixsort::{PaddingInfo, PhaseTimings, ProcessedLine, SortConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOp {
+    /// Keys in any input
+    Union,
+    /// Keys in every input
+    Intersect,
+    /// Keys in the first input but in none of the others
+    Difference,
+}
+
+/// The lines of all inputs, sorted together, with the index one past the
+/// last line of each input.
+pub struct SortedInputs {
+    pub processed: Vec<ProcessedLine>,
+    pub padding_info: Option<PaddingInfo>,
+    pub timings: PhaseTimings,
+    pub input_ends: Vec<usize>,
+}
+
+/// Sorts the inputs together in a single pass, so `sort_by` modes that look
+/// at all lines (`SortBy::Frequency`) see them all. Indices are positions in
+/// the concatenated inputs, counting lines `exclude_no_word` dropped, so
+/// `input_ends` traces lines back to their input.
+pub fn sort_inputs(config: &SortConfig, inputs: Vec<Vec<String>>) -> SortedInputs {
+    let mut lines = Vec::new();
+    let mut input_ends = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        lines.extend(input);
+        input_ends.push(lines.len());
+    }
+
+    let (processed, padding_info, timings) = config.process_lines_timed(lines);
+    SortedInputs {
+        processed,
+        padding_info,
+        timings,
+        input_ends,
+    }
+}
+
+/// Whether a group of duplicates, given the ends of the inputs, belongs in
+/// the result of `op`.
+pub fn accepts(op: SetOp, input_ends: &[usize], group: &[ProcessedLine]) -> bool {
+    let mut found = vec![false; input_ends.len()];
+    for line in group {
+        found[input_ends.partition_point(|&end| end <= line.index)] = true;
+    }
+
+    match op {
+        SetOp::Union => true,
+        SetOp::Intersect => found.iter().all(|&found| found),
+        SetOp::Difference => found[0] && !found[1..].iter().any(|&found| found),
+    }
+}
@@ -0,0 +1,336 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+use suffixsort::{PaddingInfo, PhaseTimings, ProcessedLine, SortConfig};
+
+/// Buffered input below this size is never spilled, however small the
+/// budget, so tiny budgets do not produce one run per line.
+const MIN_RUN_BYTES: usize = 1 << 20;
+
+/// The most runs merged at once, which bounds the number of open files.
+const MAX_FAN_IN: usize = 64;
+
+/// Sorts lines within a memory budget by spilling sorted runs to temporary
+/// files and merging them at the end.
+pub struct SpillSorter<'a> {
+    config: &'a SortConfig,
+    max_memory: usize,
+    buffer: Vec<String>,
+    buffered_bytes: usize,
+    next_index: usize,
+    /// Lines sorted so far, i.e. without those the library dropped
+    line_count: usize,
+    /// Spilled runs by merge level: every `MAX_FAN_IN` runs of one level are
+    /// merged into a single run of the next
+    runs: Vec<Vec<File>>,
+    padding_info: Option<PaddingInfo>,
+    timings: PhaseTimings,
+}
+
+/// Sorted output: either everything fit in memory, or the sorted runs on disk
+/// are merged lazily while writing, or (for `--section-marker`) sorted
+/// sections between lines kept in place.
+pub enum Sorted {
+    InMemory(Vec<ProcessedLine>),
+    Merged(Box<RunMerger>),
+    Sections(Vec<crate::sections::Section>),
+}
+
+impl<'a> SpillSorter<'a> {
+    pub fn new(config: &'a SortConfig, max_memory: usize) -> Self {
+        Self {
+            config,
+            max_memory,
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            next_index: 0,
+            line_count: 0,
+            runs: Vec::new(),
+            padding_info: None,
+            timings: PhaseTimings::default(),
+        }
+    }
+
+    pub fn push(&mut self, line: String) -> io::Result<()> {
+        self.buffered_bytes += estimated_size(&line);
+        self.buffer.push(line);
+
+        if self.buffered_bytes > self.max_memory.max(MIN_RUN_BYTES) {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the sorted lines along with the combined padding information
+    /// and the accumulated timings.
+    pub fn finish(mut self) -> io::Result<(Sorted, Option<PaddingInfo>, PhaseTimings)> {
+        if self.runs.is_empty() {
+            let processed = self.process_buffer();
+            return Ok((Sorted::InMemory(processed), self.padding_info, self.timings));
+        }
+
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let mut runs: Vec<File> = std::mem::take(&mut self.runs)
+            .into_iter()
+            .flatten()
+            .collect();
+        while runs.len() > MAX_FAN_IN {
+            let batch = runs.drain(..MAX_FAN_IN).collect();
+            runs.push(self.merge_runs(batch)?);
+        }
+
+        log::info!("merging {} sorted runs", runs.len());
+        let merger = RunMerger::new(Arc::new(self.config.clone()), self.line_count, runs)?;
+        Ok((
+            Sorted::Merged(Box::new(merger)),
+            self.padding_info,
+            self.timings,
+        ))
+    }
+
+    fn process_buffer(&mut self) -> Vec<ProcessedLine> {
+        let lines = std::mem::take(&mut self.buffer);
+        let offset = self.next_index;
+        self.next_index += lines.len();
+        self.buffered_bytes = 0;
+
+        let (mut processed, padding_info, timings) = self.config.process_lines_timed(lines);
+
+        // Chunk-local indices are shifted so ties merge in global input order
+        for p in &mut processed {
+            p.index += offset;
+        }
+
+        self.line_count += processed.len();
+        self.timings.prepare += timings.prepare;
+        self.timings.sort += timings.sort;
+
+        self.padding_info = crate::combine_padding(self.padding_info, padding_info);
+
+        processed
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let processed = self.process_buffer();
+        let file = write_run(processed.iter().map(Ok))?;
+
+        if self.runs.is_empty() {
+            self.runs.push(Vec::new());
+        }
+        self.runs[0].push(file);
+        log::info!(
+            "spilled run {} ({} lines) to a temporary file",
+            self.runs[0].len(),
+            processed.len()
+        );
+
+        // Merge full levels upwards so that few files are open at a time
+        let mut level = 0;
+        while self.runs[level].len() == MAX_FAN_IN {
+            let batch = std::mem::take(&mut self.runs[level]);
+            let merged = self.merge_runs(batch)?;
+            level += 1;
+            if self.runs.len() == level {
+                self.runs.push(Vec::new());
+            }
+            self.runs[level].push(merged);
+        }
+        Ok(())
+    }
+
+    /// Merges `runs` into a single run in a new temporary file.
+    fn merge_runs(&self, runs: Vec<File>) -> io::Result<File> {
+        log::info!("merging {} sorted runs into one", runs.len());
+        let merger = RunMerger::new(Arc::new(self.config.clone()), 0, runs)?;
+        write_run(merger)
+    }
+}
+
+/// Writes sorted lines to a new temporary file, rewound for reading.
+fn write_run<L: std::borrow::Borrow<ProcessedLine>>(
+    lines: impl Iterator<Item = io::Result<L>>,
+) -> io::Result<File> {
+    let mut file = tempfile::tempfile()?;
+    {
+        let mut writer = BufWriter::new(&mut file);
+        for p in lines {
+            write_record(&mut writer, p?.borrow())?;
+        }
+        writer.flush()?;
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Rough in-memory footprint of a line once processed: the original, a key
+/// of up to the same size and its reversed copy in the sort arena, and the
+/// `ProcessedLine` bookkeeping.
+fn estimated_size(line: &str) -> usize {
+    3 * line.len() + std::mem::size_of::<ProcessedLine>()
+}
+
+/// K-way merge over sorted run files.
+pub struct RunMerger {
+    config: Arc<SortConfig>,
+    line_count: usize,
+    readers: Vec<BufReader<File>>,
+    /// The next line of every run that has one left
+    heads: BinaryHeap<Head>,
+}
+
+/// The next line of a run, ordered so that `BinaryHeap`, a max-heap, pops
+/// the smallest line first.
+struct Head {
+    line: ProcessedLine,
+    run: usize,
+    config: Arc<SortConfig>,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.config.compare_lines(&other.line, &self.line)
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+impl RunMerger {
+    fn new(config: Arc<SortConfig>, line_count: usize, runs: Vec<File>) -> io::Result<Self> {
+        let mut readers: Vec<_> = runs.into_iter().map(BufReader::new).collect();
+        let mut heads = BinaryHeap::with_capacity(readers.len());
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(line) = read_record(reader)? {
+                heads.push(Head {
+                    line,
+                    run,
+                    config: Arc::clone(&config),
+                });
+            }
+        }
+
+        Ok(Self {
+            config,
+            line_count,
+            readers,
+            heads,
+        })
+    }
+
+    /// The total number of lines in the runs.
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+}
+
+impl Iterator for RunMerger {
+    type Item = io::Result<ProcessedLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Head { line, run, .. } = self.heads.pop()?;
+        match read_record(&mut self.readers[run]) {
+            Ok(Some(next)) => self.heads.push(Head {
+                line: next,
+                run,
+                config: Arc::clone(&self.config),
+            }),
+            Ok(None) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(line))
+    }
+}
+
+const NONE: u64 = u64::MAX;
+
+fn write_record(writer: &mut impl Write, p: &ProcessedLine) -> io::Result<()> {
+    writer.write_all(&(p.index as u64).to_le_bytes())?;
+    writer.write_all(&p.visual_start.map_or(NONE, |v| v as u64).to_le_bytes())?;
+    writer.write_all(&p.word_length.map_or(NONE, |v| v as u64).to_le_bytes())?;
+    write_bytes(writer, p.key.as_bytes())?;
+    write_bytes(writer, p.original.as_bytes())
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_record(reader: &mut impl Read) -> io::Result<Option<ProcessedLine>> {
+    let index = match read_u64(reader) {
+        Ok(index) => index as usize,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let visual_start = read_u64(reader)?;
+    let word_length = read_u64(reader)?;
+    let key = read_string(reader)?;
+    let original = read_string(reader)?;
+
+    Ok(Some(ProcessedLine {
+        original,
+        key,
+        index,
+        visual_start: (visual_start != NONE).then_some(visual_start as usize),
+        word_length: (word_length != NONE).then_some(word_length as usize),
+    }))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u64(reader)? as usize;
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Parses sizes like `512K`, `100M` or `2G` (binary multiples) into bytes.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &s[digits.len()..];
+
+    let multiplier: usize = match suffix.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" | "KI" => 1 << 10,
+        "M" | "MI" => 1 << 20,
+        "G" | "GI" => 1 << 30,
+        "T" | "TI" => 1 << 40,
+        _ => return Err(format!("unknown size suffix '{}'", suffix)),
+    };
+
+    let value: usize = digits
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
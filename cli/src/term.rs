@@ -0,0 +1,42 @@
+//! Terminal size detection for `--align-right-edge`.
+
+use std::io;
+use std::io::IsTerminal;
+
+/// Columns assumed when stdout is not a terminal and `COLUMNS` is not set.
+const DEFAULT_WIDTH: usize = 80;
+
+/// The width of the terminal stdout writes to, in columns. When stdout is
+/// redirected (or the size cannot be read) `COLUMNS` is used, as shells set
+/// it, and failing that 80 columns.
+pub fn width() -> usize {
+    if io::stdout().is_terminal()
+        && let Some(width) = terminal_width()
+    {
+        return width;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(unix)]
+fn terminal_width() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` through the pointer, which
+    // points to one that lives for the whole call
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> Option<usize> {
+    None
+}
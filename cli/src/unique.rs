@@ -0,0 +1,165 @@
+//! `-u`, `--count`, `--only-duplicates` and `--only-unique`: output one line
+//! per group of duplicates, or only the lines that do (or do not) have
+//! duplicates.
+
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::io;
+use std::iter::Peekable;
+use suffixsort::{ProcessedLine, SortConfig};
+
+/// What makes two lines duplicates of each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum UniqueBy {
+    /// Equal keys, i.e. lines that only differ in the tie-break
+    Key,
+    /// Byte-identical lines
+    Line,
+}
+
+/// Which line of a group of duplicates `-u` keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Keep {
+    /// The first line in sorted order
+    First,
+    /// The last line in sorted order, e.g. the most recent one with
+    /// `--tiebreak index`
+    Last,
+}
+
+/// How `--count` shows the size of each group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CountFormat {
+    /// The count before the line, like `uniq -c`
+    Prefix,
+    /// The count after the line, separated by a tab
+    Suffix,
+    /// The group's share of all sorted lines before the line
+    Percent,
+}
+
+/// Splits sorted lines into runs of lines with equal keys.
+struct KeyGroups<'a, I: Iterator> {
+    config: &'a SortConfig,
+    lines: Peekable<I>,
+}
+
+impl<I> Iterator for KeyGroups<'_, I>
+where
+    I: Iterator<Item = io::Result<ProcessedLine>>,
+{
+    type Item = io::Result<Vec<ProcessedLine>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut group = match self.lines.next()? {
+            Ok(line) => vec![line],
+            Err(e) => return Some(Err(e)),
+        };
+
+        // An error ends the group; it is returned by the next call
+        let config = self.config;
+        while let Some(Ok(line)) = self.lines.next_if(|next| {
+            next.as_ref()
+                .is_ok_and(|next| config.compare_keys(&group[0], next).is_eq())
+        }) {
+            group.push(line);
+        }
+
+        Some(Ok(group))
+    }
+}
+
+/// Groups sorted lines into duplicates according to `by`, in sorted order.
+/// Identical lines are grouped even if the tie-break does not make them
+/// adjacent.
+pub fn groups<'a>(
+    config: &'a SortConfig,
+    by: UniqueBy,
+    sorted: impl Iterator<Item = io::Result<ProcessedLine>> + 'a,
+) -> impl Iterator<Item = io::Result<Vec<ProcessedLine>>> + 'a {
+    let key_groups = KeyGroups {
+        config,
+        lines: sorted.peekable(),
+    };
+
+    key_groups.flat_map(move |group| match group {
+        Ok(group) => split(group, by).into_iter().map(Ok).collect(),
+        Err(e) => vec![Err(e)],
+    })
+}
+
+/// Splits a group of lines with equal keys into groups of identical lines
+/// for `UniqueBy::Line`, in order of their first line.
+fn split(group: Vec<ProcessedLine>, by: UniqueBy) -> Vec<Vec<ProcessedLine>> {
+    if by == UniqueBy::Key || group.len() == 1 {
+        return vec![group];
+    }
+
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<ProcessedLine>> = Vec::new();
+    for line in group {
+        match positions.get(&line.original) {
+            Some(&i) => groups[i].push(line),
+            None => {
+                positions.insert(line.original.clone(), groups.len());
+                groups.push(vec![line]);
+            }
+        }
+    }
+    groups
+}
+
+/// Keeps the groups of duplicates that `keep_group` accepts, and of each
+/// either the one line picked by `keep` or all of its lines, along with the
+/// size of its group.
+pub fn select<'a>(
+    config: &'a SortConfig,
+    by: UniqueBy,
+    keep: Option<Keep>,
+    keep_group: impl Fn(&[ProcessedLine]) -> bool + 'a,
+    sorted: impl Iterator<Item = io::Result<ProcessedLine>> + 'a,
+) -> impl Iterator<Item = io::Result<(ProcessedLine, usize)>> + 'a {
+    groups(config, by, sorted)
+        .filter(move |group| group.as_ref().map_or(true, |group| keep_group(group)))
+        .flat_map(move |group| match group {
+            Ok(mut group) => {
+                let count = group.len();
+                match keep {
+                    Some(Keep::First) => group.truncate(1),
+                    Some(Keep::Last) => {
+                        group.drain(..group.len() - 1);
+                    }
+                    None => {}
+                }
+                group.into_iter().map(|line| Ok((line, count))).collect()
+            }
+            Err(e) => vec![Err(e)],
+        })
+}
+
+/// Adds the size of its group to the output of `line`, out of `total` sorted
+/// lines.
+pub fn with_count(
+    mut line: ProcessedLine,
+    count: usize,
+    total: usize,
+    format: CountFormat,
+) -> ProcessedLine {
+    let decorate = |text: &str| match format {
+        CountFormat::Prefix => format!("{:>7} {}", count, text),
+        CountFormat::Suffix => format!("{}\t{}", text, count),
+        CountFormat::Percent => {
+            let percent = 100.0 * count as f64 / total.max(1) as f64;
+            format!("{:>6.2}% {}", percent, text)
+        }
+    };
+    let original = decorate(&line.original);
+    // Keep the key's position pointing at the key after a prefix
+    let prefix = original.chars().count() - line.original.chars().count();
+    if format != CountFormat::Suffix {
+        line.visual_start = line.visual_start.map(|start| start + prefix);
+    }
+    line.original = original;
+    line.key = decorate(&line.key);
+    line
+}
@@ -0,0 +1,33 @@
+//! `--version`, and with `--verbose` what the binary was built from, so bug
+//! reports say which code paths it contains.
+
+/// Optional cargo features of ssort and whether this build has them.
+const FEATURES: [(&str, bool); 2] = [
+    ("scripting", cfg!(feature = "scripting")),
+    ("plugins", cfg!(feature = "plugins")),
+];
+
+pub fn print(verbose: bool) {
+    println!("ssort {}", env!("CARGO_PKG_VERSION"));
+    if !verbose {
+        return;
+    }
+
+    let features: Vec<&str> = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    println!("commit:   {}", env!("SSORT_GIT_COMMIT"));
+    println!("target:   {}", env!("SSORT_TARGET"));
+    println!("profile:  {}", env!("SSORT_PROFILE"));
+    println!(
+        "features: {}",
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.join(", ")
+        }
+    );
+    println!("threads:  {}", rayon::current_num_threads());
+}
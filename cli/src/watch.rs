@@ -0,0 +1,94 @@
+use notify::{Event, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for more events after a change before re-sorting, so a
+/// burst of writes from an editor triggers a single run.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Runs `sort` once, then again every time one of `files` changes.
+///
+/// The parent directories are watched rather than the files themselves, so
+/// editors that save by writing a new file and renaming it over the old one
+/// are picked up too. `outputs` are the files `sort` writes; they must not be
+/// among `files`, or every run would trigger the next. This only returns on
+/// error.
+pub fn watch(
+    files: &[String],
+    outputs: &[String],
+    mut sort: impl FnMut() -> io::Result<()>,
+) -> io::Result<()> {
+    if files.is_empty() || files.iter().any(|f| f == "-") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--watch needs input files and cannot read from stdin",
+        ));
+    }
+
+    let watched: HashSet<PathBuf> = files
+        .iter()
+        .map(|f| absolute(Path::new(f)))
+        .collect::<io::Result<_>>()?;
+    for output in outputs {
+        if watched.contains(&absolute(Path::new(output))?) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--watch cannot write to its input '{}'", output),
+            ));
+        }
+    }
+    let directories: HashSet<&Path> = watched.iter().filter_map(|p| p.parent()).collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+    for directory in directories {
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+    }
+
+    sort()?;
+
+    let is_relevant = |event: &notify::Result<Event>| match event {
+        Ok(event) => {
+            (event.kind.is_modify() || event.kind.is_create())
+                && event.paths.iter().any(|p| watched.contains(p))
+        }
+        Err(_) => false,
+    };
+
+    loop {
+        let event = rx.recv().map_err(io::Error::other)?;
+        if !is_relevant(&event) {
+            continue;
+        }
+
+        // Swallow the rest of the burst
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        log::info!("input changed, sorting again");
+        if let Err(e) = sort() {
+            // Keep watching; the file may be mid-save and fine on the next event
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// Makes `path` absolute without requiring it to exist, resolving symlinks in
+/// its directory so it matches the paths reported by the watcher.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+        _ => std::env::current_dir()?.canonicalize()?,
+    };
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}': not a file", path.display()),
+        )
+    })?;
+    Ok(directory.join(name))
+}
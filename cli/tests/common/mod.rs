@@ -0,0 +1,52 @@
+//! Running the ssort binary on files in a temporary directory.
+
+// Every test crate compiles this module but uses only some of it
+#![allow(dead_code)]
+
+use std::fs;
+use std::process::{Command, Output};
+
+/// A temporary directory holding the input files of a test.
+pub struct Dir(tempfile::TempDir);
+
+impl Dir {
+    /// Creates the files with the given names and contents.
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+        Dir(dir)
+    }
+
+    /// Runs ssort with `args` in the directory and returns what it wrote,
+    /// failing the test if it does not succeed.
+    pub fn ssort(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs ssort with `args` in the directory and returns its error
+    /// message, failing the test if it exits with anything but status 2.
+    pub fn ssort_error(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert_eq!(output.status.code(), Some(2), "{output:?}");
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_ssort"))
+            .current_dir(self.0.path())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+}
+
+/// Runs ssort with `args` on files with the given contents, in order.
+pub fn ssort(args: &[&str], inputs: &[(&str, &str)]) -> String {
+    let names = inputs.iter().map(|(name, _)| *name);
+    let args: Vec<&str> = args.iter().copied().chain(names).collect();
+    Dir::new(inputs).ssort(&args)
+}
@@ -0,0 +1,25 @@
+//! `ssort endswith`, on input and on a saved index.
+
+mod common;
+
+use common::Dir;
+
+#[test]
+fn save_index_then_query_it() {
+    let dir = Dir::new(&[("words.txt", "thought\nBrought\nsight\n")]);
+    let found = dir.ssort(&[
+        "-i",
+        "endswith",
+        "--save-index",
+        "words.idx",
+        "ought",
+        "words.txt",
+    ]);
+    assert_eq!(found, "thought\nBrought\n");
+
+    // The index prepares queries with the options it was built with
+    assert_eq!(
+        dir.ssort(&["endswith", "--index", "words.idx", "IGHT"]),
+        "sight\n"
+    );
+}
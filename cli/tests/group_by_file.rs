@@ -0,0 +1,21 @@
+//! `--group-by-file`: each input sorted on its own under a header.
+
+mod common;
+
+use common::Dir;
+
+#[test]
+fn sorts_each_file_on_its_own() {
+    let dir = Dir::new(&[("a.txt", "b\na\n"), ("b.txt", "ab\nc\n")]);
+    let sorted = dir.ssort(&["--group-by-file", "a.txt", "b.txt"]);
+    assert_eq!(sorted, "==> a.txt <==\na\nb\n\n==> b.txt <==\nab\nc\n");
+}
+
+#[test]
+fn rejects_skip_and_take() {
+    let dir = Dir::new(&[("a.txt", "b\na\n"), ("b.txt", "ab\nc\n")]);
+    for paging in [["--skip", "1"], ["--take", "1"]] {
+        let args = [&paging[..], &["--group-by-file", "a.txt", "b.txt"]].concat();
+        assert!(dir.ssort_error(&args).contains("cannot be used with"));
+    }
+}
@@ -0,0 +1,20 @@
+//! Multi-line records with `--paragraphs` and `--record-sep`.
+
+mod common;
+
+use common::ssort;
+
+#[test]
+fn record_starting_with_blank_line() {
+    let inputs = [("quotes.txt", "%\n\nb quote\n%\na\n")];
+    assert_eq!(ssort(&["--record-sep", "%"], &inputs), "\nb quote\n%\na\n");
+}
+
+#[test]
+fn separators_only_between_records() {
+    let inputs = [("addresses.txt", "b\nx\n\na\n")];
+    assert_eq!(ssort(&["--paragraphs"], &inputs), "a\n\nb\nx\n");
+
+    let inputs = [("quotes.txt", "b\n%\na\n")];
+    assert_eq!(ssort(&["--record-sep", "%"], &inputs), "a\n%\nb\n");
+}
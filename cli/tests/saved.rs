@@ -0,0 +1,23 @@
+//! `--save-index` and `--load-index`.
+
+mod common;
+
+use common::Dir;
+
+#[test]
+fn load_with_the_same_options() {
+    let dir = Dir::new(&[("words.txt", "sing\nthought\nring\n")]);
+    dir.ssort(&["--filter", "ing", "--save-index", "words.idx", "words.txt"]);
+
+    let loaded = dir.ssort(&["--filter", "ing", "--load-index", "words.idx", "--radix"]);
+    assert_eq!(loaded, "ring\nsing\n");
+}
+
+#[test]
+fn reject_a_different_filter() {
+    let dir = Dir::new(&[("words.txt", "sing\nthought\nring\n")]);
+    dir.ssort(&["--save-index", "words.idx", "words.txt"]);
+
+    let error = dir.ssort_error(&["--filter", "ing", "--load-index", "words.idx"]);
+    assert!(error.contains("saved with different sort options"));
+}
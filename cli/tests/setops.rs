@@ -0,0 +1,35 @@
+//! Set operations and `--with-filename` across input files.
+
+mod common;
+
+use common::ssort;
+
+#[test]
+fn intersect_with_exclude_no_word() {
+    let inputs = [("c.txt", "\nx\ny\n"), ("d.txt", "y\nz\n")];
+    assert_eq!(ssort(&["-x", "--intersect"], &inputs), "y\n");
+}
+
+#[test]
+fn with_filename_and_exclude_no_word() {
+    let inputs = [("a.txt", "\nbeta\n"), ("b.txt", "gamma\n")];
+    assert_eq!(ssort(&["-x", "-H"], &inputs), "b.txt:gamma\na.txt:beta\n");
+}
+
+#[test]
+fn union_by_frequency() {
+    let inputs = [("a.txt", "b\nab\n"), ("b.txt", "ab\n")];
+    assert_eq!(ssort(&["--by-frequency", "--union"], &inputs), "ab\nb\n");
+}
+
+#[test]
+fn with_filename_in_html() {
+    let inputs = [("a.txt", "x sing\n"), ("b.txt", "ring y\n")];
+    assert_eq!(
+        ssort(&["--format", "html", "-H"], &inputs),
+        "<ol class=\"ssort\">\n\
+         <li>b.txt:<span class=\"key\">ring</span> y</li>\n\
+         <li>a.txt:<span class=\"key\">x</span> sing</li>\n\
+         </ol>\n"
+    );
+}
@@ -0,0 +1,123 @@
+use crate::{LineText, ProcessedLine, SortConfig, split_extension, split_trailing_number};
+
+/// All sort keys of a batch, stored reversed in one contiguous buffer (or
+/// as-is when keys are compared forwards), encoded so that comparing the
+/// bytes compares keys the way `SortConfig::get_comparer` does:
+///
+/// - with `domain`, the parts between dots from last to first, each followed
+///   by a zero byte, so a part sorts before longer parts it starts
+/// - with `by_extension`, a zero byte for keys without an extension, or a one
+///   byte, the extension and a zero byte, come first, then the rest of the key
+/// - with `trailing_numbers`, the number (a flag byte, the digit count, the
+///   digits and the count of leading zeros) comes before the rest of the key
+///
+/// Sorting moves small `SortEntry` values that point into this buffer instead
+/// of whole `ProcessedLine`s, which avoids one allocation per key and keeps
+/// the bytes being compared close together in memory.
+pub(crate) struct KeyArena {
+    bytes: Vec<u8>,
+}
+
+/// A key span in a `KeyArena`, plus where its line lives.
+#[derive(Clone, Copy)]
+pub(crate) struct SortEntry {
+    start: usize,
+    len: usize,
+    /// Primary sort criterion from `SortBy`, compared before the key
+    pub(crate) rank: usize,
+    /// Original input index, used as the tie-break
+    pub(crate) index: usize,
+    /// Position of the line in the slice the arena was built from
+    pub(crate) slot: usize,
+}
+
+impl KeyArena {
+    /// Builds the arena for `lines`, returning one entry per line in order.
+    pub(crate) fn build<S: LineText>(
+        lines: &[ProcessedLine<S>],
+        config: &SortConfig,
+    ) -> (Self, Vec<SortEntry>) {
+        let reversed = !config.compares_forward();
+        let total = lines.iter().map(|p| p.key.as_ref().len()).sum();
+        let mut bytes = Vec::with_capacity(total);
+        let mut entries = Vec::with_capacity(lines.len());
+
+        for (slot, p) in lines.iter().enumerate() {
+            let start = bytes.len();
+            let mut key = p.key.as_ref();
+            if config.domain {
+                for part in key.split('.').rev() {
+                    bytes.extend_from_slice(part.as_bytes());
+                    bytes.push(0);
+                }
+                key = "";
+            }
+            if config.by_extension {
+                let (base, extension) = split_extension(key);
+                if let Some(extension) = extension {
+                    bytes.push(1);
+                    push_key(&mut bytes, extension, reversed);
+                }
+                bytes.push(0);
+                key = base;
+            }
+            if config.trailing_numbers {
+                let (prefix, number) = split_trailing_number(key);
+                match number {
+                    Some((digits, zeros)) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&(digits.len() as u64).to_be_bytes());
+                        bytes.extend_from_slice(digits.as_bytes());
+                        bytes.extend_from_slice(&(zeros as u64).to_be_bytes());
+                    }
+                    None => bytes.push(0),
+                }
+                key = prefix;
+            }
+            push_key(&mut bytes, key, reversed);
+            entries.push(SortEntry {
+                start,
+                len: bytes.len() - start,
+                rank: 0,
+                index: p.index,
+                slot,
+            });
+        }
+
+        (Self { bytes }, entries)
+    }
+
+    pub(crate) fn key(&self, entry: &SortEntry) -> &[u8] {
+        &self.bytes[entry.start..entry.start + entry.len]
+    }
+}
+
+fn push_key(bytes: &mut Vec<u8>, key: &str, reversed: bool) {
+    if reversed {
+        // Reversing the characters lets a bytewise comparison of the UTF-8
+        // encoding compare keys from the last character backwards
+        let mut buf = [0; 4];
+        for c in key.chars().rev() {
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    } else {
+        bytes.extend_from_slice(key.as_bytes());
+    }
+}
+
+/// Reorders `lines` so that position `i` holds the line that was at
+/// `sources[i]`, following permutation cycles in place.
+pub(crate) fn apply_order<T>(lines: &mut [T], mut sources: Vec<usize>) {
+    for start in 0..sources.len() {
+        let mut current = start;
+        while sources[current] != usize::MAX {
+            let source = sources[current];
+            sources[current] = usize::MAX;
+            if source == start {
+                break;
+            }
+            lines.swap(current, source);
+            current = source;
+        }
+    }
+}
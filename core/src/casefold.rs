@@ -0,0 +1,83 @@
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+/// Appends the full Unicode case folding of `c` to `out`, for
+/// `FoldMode::Canonical`.
+///
+/// This differs from `char::to_lowercase` only for the characters below,
+/// which fold to something other than their lowercase. Characters that
+/// decompose canonically (e.g. Greek letters with iota subscript) are
+/// expected to be decomposed already, so they fold through their parts.
+pub(crate) fn push_folded(c: char, out: &mut String) {
+    let folded = match c {
+        'ß' | 'ẞ' => "ss",
+        'ς' => "σ",
+        'ſ' => "s",
+        'µ' => "μ",
+        '\u{345}' => "ι", // combining iota subscript
+        'ϐ' => "β",
+        'ϑ' => "θ",
+        'ϕ' => "φ",
+        'ϖ' => "π",
+        'ϰ' => "κ",
+        'ϱ' => "ρ",
+        'ϵ' => "ε",
+        'ŉ' => "ʼn",
+        'ẚ' => "aʾ",
+        'և' => "եւ",
+        'ﬀ' => "ff",
+        'ﬁ' => "fi",
+        'ﬂ' => "fl",
+        'ﬃ' => "ffi",
+        'ﬄ' => "ffl",
+        'ﬅ' | 'ﬆ' => "st",
+        'ﬓ' => "մն",
+        'ﬔ' => "մե",
+        'ﬕ' => "մի",
+        'ﬖ' => "վն",
+        'ﬗ' => "մխ",
+        'ᲀ' => "в",
+        'ᲁ' => "д",
+        'ᲂ' => "о",
+        'ᲃ' => "с",
+        'ᲄ' | 'ᲅ' => "т",
+        'ᲆ' => "ъ",
+        'ᲇ' => "ѣ",
+        'ᲈ' => "ꙋ",
+        // Cherokee folds to its capital letters, not to lowercase
+        '\u{13a0}'..='\u{13f5}' => {
+            out.push(c);
+            return;
+        }
+        '\u{13f8}'..='\u{13fd}' | '\u{ab70}'..='\u{abbf}' => {
+            let capital = match c {
+                '\u{13f8}'..='\u{13fd}' => c as u32 - 8,
+                _ => c as u32 - 0xab70 + 0x13a0,
+            };
+            out.extend(char::from_u32(capital));
+            return;
+        }
+        _ => {
+            out.extend(c.to_lowercase());
+            return;
+        }
+    };
+    out.push_str(folded);
+}
+
+/// Canonical caseless form of `text`: decomposed, case-folded and composed
+/// again (NFC), so canonically equivalent strings that differ only in case
+/// give the same result.
+pub(crate) fn canonical_caseless(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    text.nfd().for_each(|c| push_folded(c, &mut folded));
+    compose(folded)
+}
+
+/// `text` in NFC, without copying when it already is.
+pub(crate) fn compose(text: String) -> String {
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        text
+    } else {
+        text.nfc().collect()
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{Error, PaddingInfo, ProcessedLine, Result, SortConfig};
+use std::io::BufRead;
+
+/// Sorted runs of a reader's lines; see `SortConfig::process_chunks`.
+pub(crate) struct Chunks<'a, R> {
+    config: &'a SortConfig,
+    reader: R,
+    chunk_size: usize,
+    /// Lines read so far, i.e. the input index of the next one
+    read: usize,
+    buf: Vec<u8>,
+    /// Set after an error, which ends the runs
+    done: bool,
+}
+
+impl<'a, R: BufRead> Chunks<'a, R> {
+    pub(crate) fn new(config: &'a SortConfig, reader: R, chunk_size: usize) -> Self {
+        Self {
+            config,
+            reader,
+            chunk_size: chunk_size.max(1),
+            read: 0,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn read_chunk(&mut self) -> Result<Vec<String>> {
+        let mut lines = Vec::with_capacity(self.chunk_size);
+        while lines.len() < self.chunk_size {
+            match read_line(&mut self.reader, &mut self.buf, self.read + lines.len() + 1)? {
+                Some(line) => lines.push(line),
+                None => break,
+            }
+        }
+        Ok(lines)
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<'_, R> {
+    type Item = Result<(Vec<ProcessedLine>, Option<PaddingInfo>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let lines = match self.read_chunk() {
+            Ok(lines) if lines.is_empty() => return None,
+            Ok(lines) => lines,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+
+        let offset = self.read;
+        self.read += lines.len();
+        log::debug!("sorting chunk of lines {}..{}", offset, self.read);
+
+        let (mut processed, padding_info) = self.config.process_lines(lines);
+        // Chunk-local indices are shifted so ties merge in input order
+        for p in &mut processed {
+            p.index += offset;
+        }
+        Some(Ok((processed, padding_info)))
+    }
+}
+
+/// Reads the next line without its `\n` or `\r\n`, or `None` at the end of
+/// input. `number` (starting at 1) is reported if it is not valid UTF-8; the
+/// line is consumed either way, so callers may skip it and read on. `buf` is
+/// scratch space reused between calls.
+pub fn read_line(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    number: usize,
+) -> Result<Option<String>> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    }
+    String::from_utf8(std::mem::take(buf))
+        .map(Some)
+        .map_err(|_| Error::InvalidUtf8 { line: number })
+}
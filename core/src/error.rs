@@ -0,0 +1,47 @@
+use std::io;
+
+/// Errors returned by the fallible parts of the library.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Options that cannot be used together, or an option with a bad value
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// A line of input that is not valid UTF-8 (line numbers start at 1)
+    #[error("line {line}: invalid UTF-8")]
+    InvalidUtf8 { line: usize },
+
+    /// A file that is not a valid `SuffixIndex` or `SavedSort`
+    #[error("invalid index: {0}")]
+    InvalidIndex(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::InvalidConfig(_) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            Error::InvalidUtf8 { .. } | Error::InvalidIndex(_) => {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            }
+        }
+    }
+}
+
+/// The first place where lines are out of order; see
+/// `SortConfig::is_sorted`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {}: disorder: {}", .index + 1, .line)]
+pub struct Disorder {
+    /// Position of `line` in the checked lines (starting at 0)
+    pub index: usize,
+    /// The line before `line`, which should have come after it
+    pub previous: String,
+    pub line: String,
+}
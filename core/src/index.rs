@@ -0,0 +1,465 @@
+//! A lookup structure over keys read from the end (enabled with the `index`
+//! feature).
+//!
+//! `SuffixIndex` keeps the lines sorted by their reversed keys, stored back
+//! to back in one buffer. Reversed keys that start with a given prefix form
+//! one block in that order, the way the leaves below a node of a reversed-key
+//! trie do, so ends-with and longest-suffix-match queries take O(log n)
+//! comparisons instead of a scan, without the memory of a pointer-based trie.
+//!
+//! `SavedSort` stores the result of `process_lines` instead, so a large static
+//! input can be output again without being sorted again.
+
+use crate::{Error, FoldMode, PaddingInfo, Phonetic, ProcessedLine, Result, SortConfig};
+use rayon::prelude::*;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+
+const MAGIC: &[u8; 8] = b"SSORTIDX";
+const SAVED_MAGIC: &[u8; 8] = b"SSORTSRT";
+/// Version 2 added the phonetic algorithm to the index's key options
+const VERSION: u32 = 2;
+const SAVED_VERSION: u32 = 1;
+
+const NORMALIZE: u8 = 1;
+const IGNORE_CASE: u8 = 2;
+const CANONICAL_FOLD: u8 = 4;
+const ASCII_KEY: u8 = 8;
+const IGNORE_SYMBOLS: u8 = 16;
+const IGNORE_NONPRINTING: u8 = 32;
+const TRIM_KEY: u8 = 64;
+const SQUEEZE_WHITESPACE: u8 = 128;
+
+/// Sorted lines with their keys and padding, as returned by
+/// `SortConfig::process_lines`, in a form that can be written to disk.
+///
+/// The lines are only in order for the options they were sorted with;
+/// `options` is free for the caller to record those and compare them when
+/// loading.
+///
+/// # Example
+/// ```
+/// use suffixsort::SortConfig;
+/// use suffixsort::index::SavedSort;
+///
+/// let lines = vec!["sing".to_string(), "thought".to_string()];
+/// let (lines, padding_info) = SortConfig::default().process_lines(lines);
+/// let saved = SavedSort { lines, padding_info, options: String::new() };
+///
+/// let mut file = Vec::new();
+/// saved.write_to(&mut file)?;
+/// let loaded = SavedSort::read_from(&file[..])?;
+/// assert_eq!(loaded.lines[0].original, "sing");
+/// # Ok::<(), suffixsort::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SavedSort {
+    pub lines: Vec<ProcessedLine>,
+    pub padding_info: Option<PaddingInfo>,
+    /// A description of the sort options, stored as is
+    pub options: String,
+}
+
+/// Lines indexed by their keys read from the end; see the module
+/// documentation. Build one with `SortConfig::build_index`.
+#[derive(Debug, Clone)]
+pub struct SuffixIndex {
+    /// The lines in order of their reversed keys
+    lines: Vec<String>,
+    /// All reversed keys, back to back
+    keys: String,
+    /// Where each reversed key ends in `keys`
+    ends: Vec<usize>,
+    /// The options keys were prepared with (see `SortConfig::prepare_key`),
+    /// to prepare queries the same way
+    query: SortConfig,
+}
+
+impl SortConfig {
+    /// Builds a `SuffixIndex` over `lines`, with keys extracted as for
+    /// sorting. Lines that `filter` or `exclude_no_word` would drop are left
+    /// out.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let lines = ["thought", "brought", "taught", "caught", "sing"];
+    /// let lines = lines.iter().map(|s| s.to_string()).collect();
+    /// let index = SortConfig::default().build_index(lines);
+    ///
+    /// assert_eq!(index.ends_with("ought"), ["thought", "brought"]);
+    /// assert_eq!(index.longest_suffix_match("fought"), Some(("thought", 5)));
+    /// ```
+    pub fn build_index(&self, lines: Vec<String>) -> SuffixIndex {
+        let mut entries: Vec<(String, usize, String)> = self
+            .extract_keys(lines)
+            .into_par_iter()
+            .map(|p| (p.key.chars().rev().collect(), p.index, p.original))
+            .collect();
+        entries.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut index = SuffixIndex {
+            lines: Vec::with_capacity(entries.len()),
+            keys: String::new(),
+            ends: Vec::with_capacity(entries.len()),
+            query: self.key_preparation(),
+        };
+        for (key, _, line) in entries {
+            index.push(&key, line);
+        }
+        index
+    }
+
+    /// A configuration with only the options of `self` that `prepare_key`
+    /// reads.
+    fn key_preparation(&self) -> SortConfig {
+        SortConfig {
+            normalize: self.normalize,
+            ignore_case: self.ignore_case,
+            fold_mode: self.fold_mode,
+            ascii_key: self.ascii_key,
+            ignore_symbols: self.ignore_symbols,
+            ignore_nonprinting: self.ignore_nonprinting,
+            trim_key: self.trim_key,
+            squeeze_whitespace: self.squeeze_whitespace,
+            phonetic: self.phonetic,
+            ..SortConfig::default()
+        }
+    }
+}
+
+impl SuffixIndex {
+    /// The number of indexed lines.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// All indexed lines, in suffix order of their keys.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// The lines whose keys end with `suffix`, in suffix order. `suffix` is
+    /// prepared like the keys were (normalized, case-folded and so on), with
+    /// options that are saved along with the index.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    /// use suffixsort::index::SuffixIndex;
+    ///
+    /// let config = SortConfig {
+    ///     ascii_key: true,
+    ///     ..SortConfig::default()
+    /// };
+    /// let index = config.build_index(vec!["café".to_string(), "naïve".to_string()]);
+    ///
+    /// let mut file = Vec::new();
+    /// index.write_to(&mut file)?;
+    /// let index = SuffixIndex::read_from(&file[..])?;
+    /// assert_eq!(index.ends_with("fé"), ["café"]);
+    /// # Ok::<(), suffixsort::Error>(())
+    /// ```
+    pub fn ends_with(&self, suffix: &str) -> &[String] {
+        let reversed: String = self.prepare(suffix).chars().rev().collect();
+        &self.lines[self.prefix_range(&reversed)]
+    }
+
+    /// The line whose key shares the longest ending with `word`, along with
+    /// the length of that ending in characters, or `None` if the index is
+    /// empty. Of several equally good lines the first in suffix order wins.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let lines = ["cb", "ab", "bb"].iter().map(|s| s.to_string()).collect();
+    /// let index = SortConfig::default().build_index(lines);
+    ///
+    /// assert_eq!(index.longest_suffix_match("zb"), Some(("ab", 1)));
+    /// ```
+    pub fn longest_suffix_match(&self, word: &str) -> Option<(&str, usize)> {
+        let reversed: String = self.prepare(word).chars().rev().collect();
+
+        // The keys sharing the most with `word` surround its insertion point
+        let position = self.partition_point(0..self.len(), |key| key < reversed.as_str());
+        let candidates = position.saturating_sub(1)..(position + 1).min(self.len());
+
+        let shared = candidates
+            .map(|i| common_prefix_chars(self.key(i), &reversed))
+            .max()?;
+
+        // Every key sharing that much starts with the same characters, and
+        // those keys are next to each other; take the first
+        let prefix_len = reversed
+            .char_indices()
+            .nth(shared)
+            .map_or(reversed.len(), |(offset, _)| offset);
+        let first = self.prefix_range(&reversed[..prefix_len]).start;
+        Some((self.lines[first].as_str(), shared))
+    }
+
+    /// Writes the index in a compact binary format that `read_from` loads.
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        let query = &self.query;
+        let canonical = query.fold_mode == FoldMode::Canonical;
+        let flags = (query.normalize as u8 * NORMALIZE)
+            | (query.ignore_case as u8 * IGNORE_CASE)
+            | (canonical as u8 * CANONICAL_FOLD)
+            | (query.ascii_key as u8 * ASCII_KEY)
+            | (query.ignore_symbols as u8 * IGNORE_SYMBOLS)
+            | (query.ignore_nonprinting as u8 * IGNORE_NONPRINTING)
+            | (query.trim_key as u8 * TRIM_KEY)
+            | (query.squeeze_whitespace as u8 * SQUEEZE_WHITESPACE);
+
+        write_header(&mut writer, MAGIC, VERSION)?;
+        writer.write_all(&[flags, encode_phonetic(query.phonetic)])?;
+        write_u64(&mut writer, self.len() as u64)?;
+        for i in 0..self.len() {
+            write_bytes(&mut writer, self.key(i).as_bytes())?;
+            write_bytes(&mut writer, self.lines[i].as_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads an index written by `write_to`.
+    pub fn read_from(reader: impl Read) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        read_header(&mut reader, MAGIC, VERSION)?;
+        let mut options = [0; 2];
+        reader.read_exact(&mut options)?;
+        let [flags, phonetic] = options;
+        let count = read_u64(&mut reader)?;
+
+        let mut index = SuffixIndex {
+            lines: Vec::new(),
+            keys: String::new(),
+            ends: Vec::new(),
+            query: SortConfig {
+                normalize: flags & NORMALIZE != 0,
+                ignore_case: flags & IGNORE_CASE != 0,
+                fold_mode: match flags & CANONICAL_FOLD {
+                    0 => FoldMode::Lowercase,
+                    _ => FoldMode::Canonical,
+                },
+                ascii_key: flags & ASCII_KEY != 0,
+                ignore_symbols: flags & IGNORE_SYMBOLS != 0,
+                ignore_nonprinting: flags & IGNORE_NONPRINTING != 0,
+                trim_key: flags & TRIM_KEY != 0,
+                squeeze_whitespace: flags & SQUEEZE_WHITESPACE != 0,
+                phonetic: decode_phonetic(phonetic)?,
+                ..SortConfig::default()
+            },
+        };
+        for _ in 0..count {
+            let key = read_string(&mut reader)?;
+            let line = read_string(&mut reader)?;
+            if !index.is_empty() && index.key(index.len() - 1) > key.as_str() {
+                return Err(Error::InvalidIndex("keys are out of order".to_string()));
+            }
+            index.push(&key, line);
+        }
+        Ok(index)
+    }
+
+    fn push(&mut self, reversed_key: &str, line: String) {
+        self.keys.push_str(reversed_key);
+        self.ends.push(self.keys.len());
+        self.lines.push(line);
+    }
+
+    /// The reversed key of line `i`.
+    fn key(&self, i: usize) -> &str {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.keys[start..self.ends[i]]
+    }
+
+    /// The positions of the reversed keys that start with `prefix`.
+    fn prefix_range(&self, prefix: &str) -> Range<usize> {
+        let start = self.partition_point(0..self.len(), |key| key < prefix);
+        let end = self.partition_point(start..self.len(), |key| key.starts_with(prefix));
+        start..end
+    }
+
+    /// The first position in `range` whose reversed key does not satisfy
+    /// `pred`, which must hold for a leading part of the range only.
+    fn partition_point(&self, mut range: Range<usize>, pred: impl Fn(&str) -> bool) -> usize {
+        while range.start < range.end {
+            let mid = range.start + (range.end - range.start) / 2;
+            if pred(self.key(mid)) {
+                range.start = mid + 1;
+            } else {
+                range.end = mid;
+            }
+        }
+        range.start
+    }
+
+    /// Prepares a query the way the keys were prepared.
+    fn prepare(&self, text: &str) -> String {
+        self.query.prepare_key(text)
+    }
+}
+
+impl SavedSort {
+    /// Writes the lines in a compact binary format that `read_from` loads.
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+
+        write_header(&mut writer, SAVED_MAGIC, SAVED_VERSION)?;
+        write_bytes(&mut writer, self.options.as_bytes())?;
+        match self.padding_info {
+            Some(padding_info) => {
+                writer.write_all(&[1, padding_info.use_end_pos as u8])?;
+                write_u64(&mut writer, padding_info.max_value as u64)?;
+                write_u64(&mut writer, padding_info.key_width as u64)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        write_u64(&mut writer, self.lines.len() as u64)?;
+        for line in &self.lines {
+            write_bytes(&mut writer, line.original.as_bytes())?;
+            write_bytes(&mut writer, line.key.as_bytes())?;
+            write_u64(&mut writer, line.index as u64)?;
+            write_option(&mut writer, line.visual_start)?;
+            write_option(&mut writer, line.word_length)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads lines written by `write_to`.
+    pub fn read_from(reader: impl Read) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        read_header(&mut reader, SAVED_MAGIC, SAVED_VERSION)?;
+        let options = read_string(&mut reader)?;
+        let mut has_padding = [0; 1];
+        reader.read_exact(&mut has_padding)?;
+        let padding_info = match has_padding[0] {
+            0 => None,
+            _ => {
+                let mut use_end_pos = [0; 1];
+                reader.read_exact(&mut use_end_pos)?;
+                Some(PaddingInfo {
+                    use_end_pos: use_end_pos[0] != 0,
+                    max_value: read_u64(&mut reader)? as usize,
+                    key_width: read_u64(&mut reader)? as usize,
+                })
+            }
+        };
+        let count = read_u64(&mut reader)?;
+
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            lines.push(ProcessedLine {
+                original: read_string(&mut reader)?,
+                key: read_string(&mut reader)?,
+                index: read_u64(&mut reader)? as usize,
+                visual_start: read_option(&mut reader)?,
+                word_length: read_option(&mut reader)?,
+            });
+        }
+        Ok(SavedSort {
+            lines,
+            padding_info,
+            options,
+        })
+    }
+}
+
+fn common_prefix_chars(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count()
+}
+
+fn encode_phonetic(phonetic: Option<Phonetic>) -> u8 {
+    match phonetic {
+        None => 0,
+        Some(Phonetic::Rhyme) => 1,
+        Some(Phonetic::Metaphone) => 2,
+        Some(Phonetic::Soundex) => 3,
+    }
+}
+
+fn decode_phonetic(byte: u8) -> Result<Option<Phonetic>> {
+    Ok(match byte {
+        0 => None,
+        1 => Some(Phonetic::Rhyme),
+        2 => Some(Phonetic::Metaphone),
+        3 => Some(Phonetic::Soundex),
+        _ => {
+            return Err(Error::InvalidIndex(format!(
+                "unknown phonetic algorithm {}",
+                byte
+            )));
+        }
+    })
+}
+
+fn write_header(writer: &mut impl Write, magic: &[u8; 8], version: u32) -> Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&version.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_header(reader: &mut impl Read, magic: &[u8; 8], expected: u32) -> Result<()> {
+    let mut found = [0; 8];
+    reader.read_exact(&mut found)?;
+    if &found != magic {
+        return Err(Error::InvalidIndex("not an ssort index".to_string()));
+    }
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    if u32::from_le_bytes(version) != expected {
+        return Err(Error::InvalidIndex(format!(
+            "unsupported version {}",
+            u32::from_le_bytes(version)
+        )));
+    }
+    Ok(())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+/// Writes `None` as `u64::MAX`, which no position or length reaches.
+fn write_option(writer: &mut impl Write, value: Option<usize>) -> Result<()> {
+    write_u64(writer, value.map_or(u64::MAX, |v| v as u64))
+}
+
+fn read_option(reader: &mut impl Read) -> Result<Option<usize>> {
+    let value = read_u64(reader)?;
+    Ok((value != u64::MAX).then_some(value as usize))
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String> {
+    let len = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(Error::InvalidIndex("truncated".to_string()));
+    }
+    String::from_utf8(bytes).map_err(|_| Error::InvalidIndex("invalid UTF-8".to_string()))
+}
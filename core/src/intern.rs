@@ -0,0 +1,46 @@
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Integer ids for the distinct keys of a batch of lines, numbered in key
+/// order so that comparing ids compares keys; see `SortAlgorithm::Interned`.
+pub(crate) struct InternedKeys {
+    /// The id of each line's key, in line order
+    pub(crate) ids: Vec<usize>,
+    /// How many lines share each key, by id
+    pub(crate) counts: Vec<usize>,
+}
+
+impl InternedKeys {
+    /// Interns `keys`, ordering the distinct ones with `compare`.
+    pub(crate) fn new<'a>(
+        keys: impl Iterator<Item = &'a str>,
+        compare: impl Fn(&str, &str) -> Ordering + Sync,
+    ) -> Self {
+        // Number the keys by first occurrence, then renumber in key order,
+        // so only distinct keys are ever compared as strings
+        let mut first_ids: HashMap<&str, usize> = HashMap::new();
+        let mut ids: Vec<usize> = keys
+            .map(|key| {
+                let next = first_ids.len();
+                *first_ids.entry(key).or_insert(next)
+            })
+            .collect();
+
+        let mut distinct: Vec<(&str, usize)> = first_ids.into_iter().collect();
+        distinct.par_sort_unstable_by(|a, b| compare(a.0, b.0));
+
+        let mut sorted_ids = vec![0; distinct.len()];
+        for (sorted_id, &(_, first_id)) in distinct.iter().enumerate() {
+            sorted_ids[first_id] = sorted_id;
+        }
+
+        let mut counts = vec![0; distinct.len()];
+        for id in &mut ids {
+            *id = sorted_ids[*id];
+            counts[*id] += 1;
+        }
+
+        Self { ids, counts }
+    }
+}
@@ -1,43 +1,684 @@
 use rayon::prelude::*;
+use regex::Regex;
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use unicode_normalization::UnicodeNormalization;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+mod arena;
+mod casefold;
+mod chunks;
+mod error;
+#[cfg(feature = "index")]
+pub mod index;
+mod intern;
+mod merge;
+mod phonetic;
+mod simd;
+mod syllables;
+
+use arena::{KeyArena, SortEntry};
+pub use chunks::read_line;
+pub use error::{Disorder, Error, Result};
+use intern::InternedKeys;
+pub use phonetic::Phonetic;
+pub use syllables::count_syllables;
 
 #[derive(Clone, Debug)]
 pub struct SortConfig {
     pub ignore_case: bool,
+    /// How `ignore_case` folds case
+    pub fold_mode: FoldMode,
     pub use_entire_line: bool,
+    /// Extract keys from the first line of multi-line records only, e.g.
+    /// paragraphs; the whole record is still output
+    pub first_line_key: bool,
+    /// Skip leading whitespace of the line when it is the key
+    pub ignore_leading_blanks: bool,
     pub dictionary_order: bool,
+    /// Non-alphabetic characters that may appear inside a dictionary-order
+    /// word, e.g. `-'` for "mother-in-law" and "don't"; defaults to `-`
+    pub word_chars: String,
+    /// Characters that start and make up a dictionary-order word
+    pub word_class: WordClass,
+    /// Find words with Unicode (UAX #29) word boundaries instead of splitting
+    /// on whitespace or scanning for `word_class` runs. Handles contractions
+    /// and scripts written without spaces.
+    pub unicode_words: bool,
     pub reverse: bool,
+    /// Keep equal keys in input order, skipping the `tiebreak` comparison
     pub stable: bool,
     pub right_align: bool,
     pub exclude_no_word: bool,
+    /// Where lines with an empty key go when they are not excluded
+    pub no_word_position: NoWordPosition,
+    /// Only lines matching this pattern are sorted and returned; like
+    /// `exclude_no_word`, dropping lines leaves the indices of the others
+    /// at their input positions
+    pub filter: Option<Regex>,
     pub word_only: bool,
     pub normalize: bool,
+    /// Collapse runs of whitespace in the key to a single space
+    pub squeeze_whitespace: bool,
+    /// Strip trailing whitespace from the key, which would otherwise be the
+    /// most significant part of the suffix comparison
+    pub trim_key: bool,
+    /// Drop control characters from the key, like `sort -i`, so stray
+    /// escapes and carriage returns in dirty data do not decide the order
+    pub ignore_nonprinting: bool,
+    /// Extract keys from the line with ANSI escape sequences (colors) removed,
+    /// see `strip_ansi`; the output keeps them
+    pub strip_ansi: bool,
+    /// Transliterate Latin letters in the key to ASCII (e.g. "Łódź" to
+    /// "Lodz") so spellings with and without diacritics sort together
+    pub ascii_key: bool,
+    /// Drop symbols and emoji (with their modifiers and joiners) from the
+    /// key, so a trailing emoji does not decide the order
+    pub ignore_symbols: bool,
+    /// Replace each word of the key by its phonetic code, so words that sound
+    /// alike (or, with `Phonetic::Rhyme`, rhyme) sort together
+    pub phonetic: Option<Phonetic>,
+    /// Number of leading words joined (with single spaces) into the key when
+    /// sorting by first word; defaults to 1
+    pub key_words: usize,
+    /// Byte range of each line used as the key, bypassing word detection;
+    /// for fixed-width records. Lines shorter than the range give a shorter
+    /// (possibly empty) key.
+    pub byte_key: Option<Range<usize>>,
+    /// Splits lines on this separator and uses field number `field` as the
+    /// key instead of the first word
+    pub field_separator: Option<String>,
+    /// 1-based field used as the key with `field_separator`; defaults to 1.
+    /// Lines with fewer fields get an empty key.
+    pub field: usize,
+    /// Use the last field with `field_separator`, however many there are,
+    /// instead of `field`
+    pub last_field: bool,
+    /// Compare field keys as a whole in ordinary (forward) order instead of
+    /// character by character from the end
+    pub field_compare: bool,
+    /// Compare every key in ordinary (forward) lexicographic order instead of
+    /// from the last character, keeping all other options. Suffix lookups
+    /// (`find_suffix_range`, `SuffixIndex`) still assume suffix order.
+    pub forward: bool,
+    /// Compare a run of ASCII digits at the end of each key as a number
+    /// first, so "file2" sorts before "file10", and the rest of the key
+    /// after that. Keys without one sort before keys with one, and equal
+    /// numbers with fewer leading zeros ("x1") before more ("x01").
+    pub trailing_numbers: bool,
+    /// Compare the part of each key after its last '.' first and the part
+    /// before it second, to group file names by type. Keys without a '.'
+    /// sort before keys with one, even an empty extension ("a" before "a.").
+    pub by_extension: bool,
+    /// Split keys on '.' and compare the parts from the last to the first,
+    /// each in ordinary order, like reverse domain names (com, example,
+    /// www). Cannot be combined with `forward`, `trailing_numbers` or
+    /// `by_extension` (see `validate`).
+    pub domain: bool,
+    pub algorithm: SortAlgorithm,
+    pub sort_by: SortBy,
+    /// Last-resort comparison for equal keys when `stable` is off
+    pub tiebreak: Tiebreak,
+    /// Custom key derivation replacing first-word/entire-line extraction; see
+    /// `with_key_fn`
+    pub key_fn: Option<KeyFn>,
+    /// Syllable counting for `SortBy::Syllables` in place of
+    /// `count_syllables`, e.g. for another language; see `with_syllable_fn`
+    pub syllable_fn: Option<SyllableFn>,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            ignore_case: false,
+            fold_mode: FoldMode::default(),
+            use_entire_line: false,
+            first_line_key: false,
+            ignore_leading_blanks: false,
+            dictionary_order: false,
+            word_chars: "-".to_string(),
+            word_class: WordClass::default(),
+            unicode_words: false,
+            reverse: false,
+            stable: false,
+            right_align: false,
+            exclude_no_word: false,
+            no_word_position: NoWordPosition::default(),
+            filter: None,
+            word_only: false,
+            normalize: false,
+            squeeze_whitespace: false,
+            trim_key: false,
+            ignore_nonprinting: false,
+            strip_ansi: false,
+            ascii_key: false,
+            ignore_symbols: false,
+            phonetic: None,
+            key_words: 1,
+            byte_key: None,
+            field_separator: None,
+            field: 1,
+            last_field: false,
+            field_compare: false,
+            forward: false,
+            trailing_numbers: false,
+            by_extension: false,
+            domain: false,
+            algorithm: SortAlgorithm::default(),
+            sort_by: SortBy::default(),
+            tiebreak: Tiebreak::default(),
+            key_fn: None,
+            syllable_fn: None,
+        }
+    }
+}
+
+type KeyFnInner = dyn for<'a> Fn(&'a str) -> Cow<'a, str> + Send + Sync;
+
+/// A user-supplied function that derives the sort key from a line.
+#[derive(Clone)]
+pub struct KeyFn(Arc<KeyFnInner>);
+
+impl fmt::Debug for KeyFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyFn(..)")
+    }
+}
+
+/// A user-supplied function that counts the syllables of a key.
+#[derive(Clone)]
+pub struct SyllableFn(Arc<dyn Fn(&str) -> usize + Send + Sync>);
+
+impl fmt::Debug for SyllableFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SyllableFn(..)")
+    }
+}
+
+/// The sorting algorithm used by `process_lines`. All produce the same order.
+///
+/// # Example
+/// ```
+/// use suffixsort::{SortAlgorithm, SortConfig};
+///
+/// let sort = |config: &SortConfig, lines: &[&str], algorithm| {
+///     let config = SortConfig {
+///         algorithm,
+///         ..config.clone()
+///     };
+///     let lines = lines.iter().map(|line| line.to_string()).collect();
+///     let (sorted, _) = config.process_lines(lines);
+///     sorted.into_iter().map(|p| p.original).collect::<Vec<_>>()
+/// };
+/// let check = |config: SortConfig, lines: &[&str], expected: &[&str]| {
+///     for algorithm in [SortAlgorithm::Comparison, SortAlgorithm::Radix, SortAlgorithm::Interned] {
+///         assert_eq!(sort(&config, lines, algorithm), expected);
+///     }
+/// };
+///
+/// let trailing_numbers = SortConfig {
+///     trailing_numbers: true,
+///     ..SortConfig::default()
+/// };
+/// check(
+///     trailing_numbers,
+///     &["x1 b", "x01 a", "x001 c", "y1 d", "x1 a"],
+///     &["x1 b", "x1 a", "y1 d", "x01 a", "x001 c"],
+/// );
+///
+/// let by_extension = SortConfig {
+///     by_extension: true,
+///     ..SortConfig::default()
+/// };
+/// check(
+///     by_extension,
+///     &["a. 1", "b 2", "a 3", "b. 4", "a 5"],
+///     &["a 3", "a 5", "b 2", "a. 1", "b. 4"],
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortAlgorithm {
+    /// Parallel comparison sort using the suffix comparator
+    #[default]
+    Comparison,
+    /// MSD radix sort over the reversed key bytes, falling back to the
+    /// comparator for small buckets. Usually faster on large, ASCII-dominated
+    /// inputs such as dictionaries.
+    Radix,
+    /// Maps every distinct key to an integer id numbered in key order, then
+    /// sorts the lines by id. Usually faster when many lines share few keys,
+    /// such as log levels or country codes, since each distinct key is
+    /// compared as a string only while numbering them.
+    Interned,
+}
+
+/// How keys are case-folded with `SortConfig::ignore_case`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FoldMode {
+    /// Lowercase every character (`str::to_lowercase`)
+    #[default]
+    Lowercase,
+    /// Unicode canonical caseless matching: decompose, apply full case
+    /// folding, then compose again (NFC), so that e.g. "STRASSE" and
+    /// "straße", or a precomposed letter and the same letter with a
+    /// combining mark, give the same key. Implies `normalize`.
+    Canonical,
 }
 
+/// Which characters make up a dictionary-order word, besides
+/// `SortConfig::word_chars`.
+#[derive(Clone, Debug, Default)]
+pub enum WordClass {
+    /// Alphabetic characters
+    #[default]
+    Alpha,
+    /// Alphabetic characters and digits, so identifiers like "sha256" are one
+    /// word
+    Alnum,
+    /// Characters matching a regex character class; see `WordClass::pattern`
+    Pattern(Regex),
+}
+
+impl WordClass {
+    /// Builds a class from a regex matching a single character, such as
+    /// `[a-z0-9_]` or `\p{Greek}`.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::WordClass;
+    ///
+    /// let class = WordClass::pattern("[a-z0-9_]").unwrap();
+    /// assert!(class.contains('_'));
+    /// assert!(!class.contains('-'));
+    /// ```
+    pub fn pattern(class: &str) -> Result<Self> {
+        Regex::new(&format!("^(?:{})$", class))
+            .map(WordClass::Pattern)
+            .map_err(|e| Error::InvalidConfig(format!("invalid word class '{}': {}", class, e)))
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            WordClass::Alpha => c.is_alphabetic(),
+            WordClass::Alnum => c.is_alphanumeric(),
+            WordClass::Pattern(regex) => regex.is_match(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+}
+
+/// What lines are primarily ordered by. Lines that rank equally are ordered
+/// by the suffix comparison of their keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortBy {
+    /// The suffix comparison of the keys alone
+    #[default]
+    Suffix,
+    /// The number of characters in the key, shortest first
+    KeyLength,
+    /// The number of lines sharing the key, most common first. Group sizes
+    /// are counted over the lines of one `process_lines` call.
+    Frequency,
+    /// The number of characters in the whole original line, shortest first
+    LineLength,
+    /// The terminal display width of the whole original line, so wide (e.g.
+    /// CJK) characters count as two columns
+    LineWidth,
+    /// The estimated number of syllables in the key, fewest first; see
+    /// `count_syllables` and `SortConfig::syllable_fn`
+    Syllables,
+}
+
+/// How lines with equal keys are ordered relative to each other unless
+/// `SortConfig::stable` is set. Lines that are still equal after the
+/// tie-break keep their input order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// Keep input order
+    #[default]
+    Index,
+    /// Compare the whole original lines from the first character
+    Line,
+    /// Compare the whole original lines from the last character, like keys
+    LineSuffix,
+    /// Compare the rest of the lines, without their keys, from the first
+    /// character, so large groups of equal keys (such as rhymes) read in
+    /// ordinary alphabetical order. Lines without a known key position
+    /// (custom keys) are compared whole, and escape sequences are left out
+    /// with `strip_ansi`.
+    Rest,
+}
+
+/// Where lines without a word (an empty key) are placed in the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoWordPosition {
+    /// Sort them by their empty key like any other line
+    #[default]
+    Sorted,
+    /// Put them before all other lines, regardless of `reverse`
+    First,
+    /// Put them after all other lines, regardless of `reverse`
+    Last,
+}
+
+/// A line with its sort key. `original` is the line itself: an owned
+/// `String` from `process_lines`, or a `&str` into the caller's lines from
+/// `process_borrowed`.
 #[derive(Debug)]
-pub struct ProcessedLine {
-    pub original: String,
-    pub key: String,
+pub struct ProcessedLine<S: LineText = String> {
+    pub original: S,
+    pub key: S::Key,
     pub index: usize,
+    /// Where the key starts in the line, in characters; `None` for lines
+    /// without a key and for custom keys
     pub visual_start: Option<usize>,
+    /// How many characters of the line the key spans, from `visual_start`
     pub word_length: Option<usize>,
 }
 
-#[derive(Debug)]
+/// The types a `ProcessedLine` can hold its line as.
+pub trait LineText: AsRef<str> + Send + Sync {
+    /// How the key is stored: a `String` for owned lines, and for borrowed
+    /// ones a `Cow` that borrows the line when the key is all of it
+    type Key: AsRef<str> + fmt::Debug + Send + Sync;
+
+    /// The key of a line whose key is the whole line, unchanged.
+    fn whole_line_key(&self) -> Self::Key;
+
+    fn owned_key(key: String) -> Self::Key;
+}
+
+impl LineText for String {
+    type Key = String;
+
+    fn whole_line_key(&self) -> String {
+        self.clone()
+    }
+
+    fn owned_key(key: String) -> String {
+        key
+    }
+}
+
+impl<'a> LineText for &'a str {
+    type Key = Cow<'a, str>;
+
+    fn whole_line_key(&self) -> Cow<'a, str> {
+        Cow::Borrowed(self)
+    }
+
+    fn owned_key(key: String) -> Cow<'a, str> {
+        Cow::Owned(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct PaddingInfo {
     pub max_value: usize,
     pub use_end_pos: bool,
+    /// Width (in characters) of the widest key, for right-aligning
+    /// word-only output
+    pub key_width: usize,
+}
+
+/// Wall-clock time spent in each phase of `SortConfig::process_lines_timed`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhaseTimings {
+    /// Key extraction, normalization, case folding and padding computation
+    pub prepare: Duration,
+    pub sort: Duration,
 }
 
 impl SortConfig {
+    /// Uses `key_fn` to derive the sort key from each line instead of taking
+    /// the first word or the entire line.
+    ///
+    /// Normalization, case folding, the suffix comparator, padding and the
+    /// parallel sort all still apply to the returned key. Lines whose key is
+    /// empty count as lines without a word for `exclude_no_word`.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    /// use std::borrow::Cow;
+    ///
+    /// // Sort "name=value" lines by their value
+    /// let config = SortConfig::default()
+    ///     .with_key_fn(|line| match line.split_once('=') {
+    ///         Some((_, value)) => Cow::Borrowed(value),
+    ///         None => Cow::Borrowed(""),
+    ///     });
+    ///
+    /// let lines = vec!["a=blue".to_string(), "b=red".to_string()];
+    /// let (sorted, _) = config.process_lines(lines);
+    /// assert_eq!(sorted[0].original, "b=red");
+    /// ```
+    pub fn with_key_fn<F>(mut self, key_fn: F) -> Self
+    where
+        F: for<'a> Fn(&'a str) -> Cow<'a, str> + Send + Sync + 'static,
+    {
+        self.key_fn = Some(KeyFn(Arc::new(key_fn)));
+        self
+    }
+
+    /// Uses `syllable_fn` to count syllables for `SortBy::Syllables` instead
+    /// of `count_syllables`, e.g. with rules for another language.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::{SortBy, SortConfig};
+    ///
+    /// // Japanese kana: one syllable (mora) per character
+    /// let config = SortConfig {
+    ///     sort_by: SortBy::Syllables,
+    ///     ..SortConfig::default()
+    /// }
+    /// .with_syllable_fn(|key| key.chars().count());
+    ///
+    /// let lines = vec!["さくら".to_string(), "はな".to_string()];
+    /// let (sorted, _) = config.process_lines(lines);
+    /// assert_eq!(sorted[0].original, "はな");
+    /// ```
+    pub fn with_syllable_fn<F>(mut self, syllable_fn: F) -> Self
+    where
+        F: Fn(&str) -> usize + Send + Sync + 'static,
+    {
+        self.syllable_fn = Some(SyllableFn(Arc::new(syllable_fn)));
+        self
+    }
+
+    /// Checks for options that cannot be used together, or have values that
+    /// mean nothing, which processing would otherwise resolve by silently
+    /// ignoring some of them: more than one of `key_fn`, `byte_key` and
+    /// `field_separator`, `domain` with `forward`, `trailing_numbers` or
+    /// `by_extension`, and a `field` of 0.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig {
+    ///     byte_key: Some(0..4),
+    ///     field_separator: Some(",".to_string()),
+    ///     ..SortConfig::default()
+    /// };
+    /// assert!(config.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        let key_sources: Vec<&str> = [
+            ("key_fn", self.key_fn.is_some()),
+            ("byte_key", self.byte_key.is_some()),
+            ("field_separator", self.field_separator.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        if key_sources.len() > 1 {
+            return Err(Error::InvalidConfig(format!(
+                "{} cannot be used together",
+                key_sources.join(", ")
+            )));
+        }
+
+        if self.domain {
+            let overridden = [
+                ("forward", self.forward),
+                ("trailing_numbers", self.trailing_numbers),
+                ("by_extension", self.by_extension),
+            ];
+            if let Some((name, _)) = overridden.iter().find(|(_, set)| *set) {
+                return Err(Error::InvalidConfig(format!(
+                    "domain cannot be used with {}",
+                    name
+                )));
+            }
+        }
+
+        if self.field == 0 {
+            return Err(Error::InvalidConfig("field numbers start at 1".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Extracts the keys of `lines` and sorts them.
+    ///
+    /// The order is fully determined by the configuration and the input:
+    /// every comparison ends with the unique input index, so the parallel
+    /// (and unstable) sorts cannot reorder equal lines differently between
+    /// runs, thread counts or `algorithm`s. This holds as long as `key_fn`,
+    /// if set, is itself deterministic.
     pub fn process_lines(&self, lines: Vec<String>) -> (Vec<ProcessedLine>, Option<PaddingInfo>) {
+        let (processed, padding_info, _) = self.process_lines_timed(lines);
+        (processed, padding_info)
+    }
+
+    /// Reads all lines from `reader` and processes them like `process_lines`.
+    ///
+    /// Line endings (`\n` or `\r\n`) are stripped. Unlike going through
+    /// `BufRead::lines`, invalid UTF-8 is reported with the offending line
+    /// number. The configuration is checked with `validate` first.
+    pub fn process_reader(
+        &self,
+        mut reader: impl BufRead,
+    ) -> Result<(Vec<ProcessedLine>, Option<PaddingInfo>)> {
+        self.validate()?;
+        let mut lines = Vec::new();
+        let mut buf = Vec::new();
+
+        while let Some(line) = chunks::read_line(&mut reader, &mut buf, lines.len() + 1)? {
+            lines.push(line);
+        }
+
+        Ok(self.process_lines(lines))
+    }
+
+    /// Reads `reader` in chunks of `chunk_size` lines and sorts each one on
+    /// its own, yielding the sorted runs as they are ready, so only one chunk
+    /// is held in memory at a time.
+    ///
+    /// Indices are positions in the whole input, so merging the runs with
+    /// `compare_lines` (e.g. after spilling them to disk) gives the order
+    /// `process_reader` would. Each run comes with its own padding
+    /// information. Reading stops at the first error, such as invalid UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let input = std::io::Cursor::new("ba\nb\na\nab\ncb\n");
+    ///
+    /// let runs: Vec<_> = config
+    ///     .process_chunks(input, 2)
+    ///     .collect::<suffixsort::Result<_>>()
+    ///     .unwrap();
+    /// assert_eq!(runs.len(), 3);
+    ///
+    /// let (first, _) = &runs[0];
+    /// assert_eq!(first[0].original, "ba");
+    /// assert_eq!(runs[2].0[0].index, 4);
+    /// ```
+    pub fn process_chunks<R: BufRead>(
+        &self,
+        reader: R,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<(Vec<ProcessedLine>, Option<PaddingInfo>)>> {
+        chunks::Chunks::new(self, reader, chunk_size)
+    }
+
+    /// Same as `process_lines`, but also reports how long key preparation and
+    /// sorting took, so callers can tell where large runs spend their time.
+    pub fn process_lines_timed(
+        &self,
+        lines: Vec<String>,
+    ) -> (Vec<ProcessedLine>, Option<PaddingInfo>, PhaseTimings) {
+        self.process(lines)
+    }
+
+    /// Like `process_lines`, but the returned lines borrow their text from
+    /// `lines` instead of owning a copy, roughly halving the memory held for
+    /// large inputs that the caller keeps around anyway. With
+    /// `use_entire_line` and no option that changes keys (case folding,
+    /// normalization and the like), keys borrow the lines too, so nothing is
+    /// copied.
+    ///
+    /// # Example
+    /// ```
+    /// use std::borrow::Cow;
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig {
+    ///     use_entire_line: true,
+    ///     ..SortConfig::default()
+    /// };
+    /// let lines = vec!["ba".to_string(), "b".to_string(), "a".to_string()];
+    ///
+    /// let (sorted, _) = config.process_borrowed(&lines);
+    /// assert!(matches!(sorted[0].key, Cow::Borrowed("a")));
+    /// let sorted: Vec<&str> = sorted.iter().map(|p| p.original).collect();
+    /// assert_eq!(sorted, ["a", "ba", "b"]);
+    /// ```
+    pub fn process_borrowed<'a, S: AsRef<str> + Sync>(
+        &self,
+        lines: &'a [S],
+    ) -> (Vec<ProcessedLine<&'a str>>, Option<PaddingInfo>) {
+        let lines = lines.iter().map(AsRef::as_ref).collect();
+        let (processed, padding_info, _) = self.process(lines);
+        (processed, padding_info)
+    }
+
+    /// `process_lines_timed` for owned or borrowed lines.
+    fn process<S: LineText>(
+        &self,
+        lines: Vec<S>,
+    ) -> (Vec<ProcessedLine<S>>, Option<PaddingInfo>, PhaseTimings) {
+        let prepare_start = Instant::now();
+
+        let line_count = lines.len();
+
         // Process lines - output formatting options should not affect processing
-        let mut processed = if self.use_entire_line {
-            self.process_lines_entire_line(&lines)
-        } else {
-            self.process_lines_standard(&lines)
-        };
+        self.log_key_extraction();
+        let mut processed = self.extract_keys(lines);
+
+        if processed.len() < line_count {
+            log::info!(
+                "dropped {} lines {}",
+                line_count - processed.len(),
+                match (self.filter.is_some(), self.exclude_no_word) {
+                    (true, true) => "not matching the filter or without a word",
+                    (true, false) => "not matching the filter",
+                    _ => "without a word",
+                }
+            );
+        }
 
         // Compute padding information if needed (purely for output formatting)
         let padding_info = if self.right_align {
@@ -46,10 +687,17 @@ impl SortConfig {
             None
         };
 
+        let sort_start = Instant::now();
+
         // Sort the processed lines
         self.sort_processed_lines(&mut processed);
 
-        (processed, padding_info)
+        let timings = PhaseTimings {
+            prepare: sort_start - prepare_start,
+            sort: sort_start.elapsed(),
+        };
+
+        (processed, padding_info, timings)
     }
 
     /// Creates a comparator closure that can be used with Rust's sort_by method.
@@ -76,31 +724,7 @@ impl SortConfig {
         let reverse = self.reverse;
 
         move |a: &str, b: &str| {
-            // Compare characters in reverse order (inverse lexicographic)
-            let mut a_iter = a.chars().rev();
-            let mut b_iter = b.chars().rev();
-
-            let mut ordering = Ordering::Equal;
-            loop {
-                match (a_iter.next(), b_iter.next()) {
-                    (Some(a_char), Some(b_char)) => {
-                        let cmp = a_char.cmp(&b_char);
-                        if cmp != Ordering::Equal {
-                            ordering = cmp;
-                            break;
-                        }
-                    }
-                    (Some(_), None) => {
-                        ordering = Ordering::Greater;
-                        break;
-                    }
-                    (None, Some(_)) => {
-                        ordering = Ordering::Less;
-                        break;
-                    }
-                    (None, None) => break,
-                }
-            }
+            let ordering = self.compare_key_text(a, b);
 
             // Apply reverse flag if needed
             if reverse {
@@ -111,136 +735,450 @@ impl SortConfig {
         }
     }
 
-    fn process_lines_entire_line(&self, lines: &[String]) -> Vec<ProcessedLine> {
-        lines
-            .par_iter()
-            .enumerate()
-            .filter_map(|(index, line)| {
-                // When using entire line, exclude_no-word means exclude empty lines
-                if self.exclude_no_word && line.is_empty() {
-                    return None;
+    /// Compares two prepared keys, before `reverse` is applied.
+    fn compare_key_text(&self, a: &str, b: &str) -> Ordering {
+        if self.domain {
+            return a.split('.').rev().cmp(b.split('.').rev());
+        }
+        if self.by_extension {
+            let (a_base, a_extension) = split_extension(a);
+            let (b_base, b_extension) = split_extension(b);
+            let forward = self.compares_forward();
+            return match (a_extension, b_extension) {
+                (Some(a_extension), Some(b_extension)) => {
+                    compare_text(a_extension, b_extension, forward)
                 }
+                (a_extension, b_extension) => a_extension.is_some().cmp(&b_extension.is_some()),
+            }
+            .then_with(|| self.compare_base(a_base, b_base));
+        }
+        self.compare_base(a, b)
+    }
 
-                // For use_entire_line, we can use the line directly as the key
-                // after applying normalization and case folding
-                let key = self.prepare_key(line);
-
-                Some(ProcessedLine {
-                    original: line.clone(),
-                    key,
-                    index,
-                    visual_start: None,
-                    word_length: None,
-                })
-            })
-            .collect()
+    /// Compares keys, or with `by_extension` the parts before the extensions.
+    fn compare_base(&self, a: &str, b: &str) -> Ordering {
+        if self.trailing_numbers {
+            compare_trailing_numbers(a, b, self.compares_forward())
+        } else {
+            compare_text(a, b, self.compares_forward())
+        }
     }
 
-    fn process_lines_standard(&self, lines: &[String]) -> Vec<ProcessedLine> {
+    /// Whether keys are compared from the first character rather than the
+    /// last, as requested by `forward`, or by `field_compare` for field keys.
+    fn compares_forward(&self) -> bool {
+        self.forward || (self.field_compare && self.field_separator.is_some())
+    }
+
+    /// Reports which key extraction `extract_keys` uses.
+    fn log_key_extraction(&self) {
+        if self.key_fn.is_some() {
+            log::debug!("extracting keys with a custom key function");
+        } else if let Some(range) = &self.byte_key {
+            log::debug!("extracting keys from bytes {}..{}", range.start, range.end);
+        } else if let Some(separator) = &self.field_separator {
+            if self.last_field {
+                log::debug!(
+                    "extracting keys from the last field split on {:?}",
+                    separator
+                );
+            } else {
+                log::debug!(
+                    "extracting keys from field {} split on {:?}",
+                    self.field,
+                    separator
+                );
+            }
+        } else if self.use_entire_line {
+            log::debug!("extracting keys from entire lines");
+        } else {
+            log::debug!(
+                "extracting keys from the first word{}",
+                if self.unicode_words {
+                    " (Unicode word boundaries)"
+                } else if self.dictionary_order {
+                    " (dictionary order)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+
+    /// Derives the key of every line, dropping lines that do not match
+    /// `filter` and, if `exclude_no_word` is set, lines without a word.
+    /// Indices are positions in `lines`, dropped lines included.
+    fn extract_keys<S: LineText>(&self, lines: Vec<S>) -> Vec<ProcessedLine<S>> {
         lines
-            .par_iter()
+            .into_par_iter()
             .enumerate()
-            .filter_map(|(index, line)| {
-                let (key, visual_start, word_length) = if self.dictionary_order {
-                    // For dictionary order, we need to track visual information
-                    let word_start = line
-                        .char_indices()
-                        .find(|(_, c)| c.is_alphabetic())
-                        .map(|(idx, _)| idx);
-
-                    match word_start {
-                        Some(start) => {
-                            // Find the end of the word, allowing dashes within the word
-                            let mut word_end = start;
-                            let mut visual_length = 0;
-                            let mut in_word = false;
-
-                            for (idx, c) in line.char_indices().skip(start) {
-                                if c.is_alphabetic() {
-                                    if !in_word {
-                                        in_word = true;
-                                    }
-                                    visual_length += 1;
-                                    word_end = idx + c.len_utf8();
-                                } else if c == '-' && in_word {
-                                    // Include dashes that are part of the word
-                                    visual_length += 1;
-                                    word_end = idx + c.len_utf8();
-                                } else if in_word {
-                                    // We've reached the end of the word
-                                    break;
-                                }
-                            }
+            .filter_map(|(index, line)| self.extract_key(index, line))
+            .collect()
+    }
 
-                            let word = line[start..word_end].to_string();
-                            let prepared_word = self.prepare_key(&word);
-                            (prepared_word, Some(start), Some(visual_length))
-                        }
-                        None => (String::new(), None, None),
-                    }
-                } else {
-                    // For non-dictionary order, extract key normally
-                    let mut start = 0;
-                    let mut end = 0;
-                    let mut in_word = false;
-
-                    for (idx, c) in line.char_indices() {
-                        if c.is_whitespace() {
-                            if in_word {
-                                end = idx;
-                                break;
+    /// Derives the key of `line`, which is at position `index`, or `None` if
+    /// `extract_keys` would drop it.
+    fn extract_key<S: LineText>(&self, index: usize, line: S) -> Option<ProcessedLine<S>> {
+        if !self.matches_filter(line.as_ref()) {
+            return None;
+        }
+
+        if let Some(key_fn) = &self.key_fn {
+            self.custom_key(index, line, key_fn)
+        } else if let Some(range) = &self.byte_key {
+            self.byte_range_key(index, line, range.clone())
+        } else if let Some(separator) = &self.field_separator {
+            self.field_key(index, line, separator)
+        } else if self.use_entire_line {
+            self.entire_line_key(index, line)
+        } else {
+            self.first_words_key(index, line)
+        }
+    }
+
+    fn entire_line_key<S: LineText>(&self, index: usize, line: S) -> Option<ProcessedLine<S>> {
+        let full = self.key_text(line.as_ref());
+        let text = if self.ignore_leading_blanks {
+            full.trim_start()
+        } else {
+            &full
+        };
+        // When using entire line, exclude_no-word means exclude empty lines
+        if self.exclude_no_word && text.is_empty() {
+            return None;
+        }
+
+        // For use_entire_line, we can use the line directly as the key
+        // after applying normalization and case folding. Borrowed
+        // lines that need neither share their text with the key.
+        let start = full.len() - text.len();
+        let (visual_start, word_length) = char_span(&full, start..full.len());
+        let key = if self.prepares_verbatim() && text.len() == line.as_ref().len() {
+            line.whole_line_key()
+        } else {
+            S::owned_key(self.prepare_key(text))
+        };
+
+        Some(ProcessedLine {
+            original: line,
+            key,
+            index,
+            visual_start,
+            word_length,
+        })
+    }
+
+    fn custom_key<S: LineText>(
+        &self,
+        index: usize,
+        line: S,
+        key_fn: &KeyFn,
+    ) -> Option<ProcessedLine<S>> {
+        let text = self.key_text(line.as_ref());
+        let text: &str = &text;
+        let key = self.prepare_key(&(key_fn.0)(text));
+
+        if self.exclude_no_word && key.is_empty() {
+            return None;
+        }
+
+        Some(ProcessedLine {
+            original: line,
+            key: S::owned_key(key),
+            index,
+            visual_start: None,
+            word_length: None,
+        })
+    }
+
+    fn byte_range_key<S: LineText>(
+        &self,
+        index: usize,
+        line: S,
+        range: Range<usize>,
+    ) -> Option<ProcessedLine<S>> {
+        let text = self.key_text(line.as_ref());
+        let text: &str = &text;
+        let bytes = text.as_bytes();
+        let start = range.start.min(bytes.len());
+        let end = range.end.clamp(start, bytes.len());
+        // The range may split a multi-byte character; keep what decodes
+        let key = self.prepare_key(&String::from_utf8_lossy(&bytes[start..end]));
+
+        if self.exclude_no_word && key.is_empty() {
+            return None;
+        }
+
+        let (visual_start, word_length) = char_span(text, start..end);
+        Some(ProcessedLine {
+            original: line,
+            key: S::owned_key(key),
+            index,
+            visual_start,
+            word_length,
+        })
+    }
+
+    fn field_key<S: LineText>(
+        &self,
+        index: usize,
+        line: S,
+        separator: &str,
+    ) -> Option<ProcessedLine<S>> {
+        let text = self.key_text(line.as_ref());
+        let text: &str = &text;
+        let field = if self.last_field {
+            text.rsplit(separator).next()
+        } else {
+            text.split(separator).nth(self.field.saturating_sub(1))
+        };
+        let key = self.prepare_key(field.unwrap_or(""));
+
+        if self.exclude_no_word && key.is_empty() {
+            return None;
+        }
+
+        let (visual_start, word_length) = match field {
+            Some(field) => {
+                let start = offset_in(text, field);
+                char_span(text, start..start + field.len())
+            }
+            None => (None, None),
+        };
+        Some(ProcessedLine {
+            original: line,
+            key: S::owned_key(key),
+            index,
+            visual_start,
+            word_length,
+        })
+    }
+
+    fn first_words_key<S: LineText>(&self, index: usize, line: S) -> Option<ProcessedLine<S>> {
+        let text = self.key_text(line.as_ref());
+        let text: &str = &text;
+        let (key, visual_start, word_length) = if self.unicode_words {
+            self.unicode_words_key(text)
+        } else if self.dictionary_order {
+            // For dictionary order, we need to track visual information
+            match self.dictionary_word(text, 0) {
+                Some((start, mut end)) => {
+                    let mut words = text[start..end].to_string();
+                    for _ in 1..self.key_words {
+                        match self.dictionary_word(text, end) {
+                            Some((next_start, next_end)) => {
+                                words.push(' ');
+                                words.push_str(&text[next_start..next_end]);
+                                end = next_end;
                             }
-                        } else if !in_word {
-                            start = idx;
-                            in_word = true;
+                            None => break,
                         }
                     }
 
-                    let key = if in_word && end == 0 {
-                        line[start..].to_string()
-                    } else if in_word {
-                        line[start..end].to_string()
-                    } else {
-                        String::new()
-                    };
+                    let (visual_start, word_length) = char_span(text, start..end);
+                    (self.prepare_key(&words), visual_start, word_length)
+                }
+                None => (String::new(), None, None),
+            }
+        } else {
+            // For non-dictionary order, join the leading whitespace-separated words
+            let mut key = String::new();
+            let mut span: Option<Range<usize>> = None;
+            for word in text.split_whitespace().take(self.key_words.max(1)) {
+                if !key.is_empty() {
+                    key.push(' ');
+                }
+                key.push_str(word);
+                let start = offset_in(text, word);
+                let start = span.map_or(start, |span| span.start);
+                span = Some(start..offset_in(text, word) + word.len());
+            }
 
-                    let prepared_key = self.prepare_key(&key);
-                    (prepared_key, None, None)
-                };
+            let (visual_start, word_length) = match span {
+                Some(span) => char_span(text, span),
+                None => (None, None),
+            };
+            (self.prepare_key(&key), visual_start, word_length)
+        };
 
-                if self.exclude_no_word && key.is_empty() {
-                    None
-                } else {
-                    Some(ProcessedLine {
-                        original: line.clone(),
-                        key,
-                        index,
-                        visual_start,
-                        word_length,
-                    })
-                }
+        if self.exclude_no_word && key.is_empty() {
+            None
+        } else {
+            Some(ProcessedLine {
+                original: line,
+                key: S::owned_key(key),
+                index,
+                visual_start,
+                word_length,
             })
-            .collect()
+        }
     }
 
-    // Helper function to prepare a key (normalize and case-fold if needed)
-    fn prepare_key(&self, key: &str) -> String {
-        let normalized = if self.normalize {
-            key.nfc().collect()
+    /// Whether `line` is kept by `filter`, if any.
+    fn matches_filter(&self, line: &str) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.is_match(line))
+    }
+
+    /// The part of `line` keys are extracted from: its first line with
+    /// `first_line_key`, otherwise all of it, without escape sequences with
+    /// `strip_ansi`.
+    fn key_text<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let text = if self.first_line_key {
+            line.split('\n').next().unwrap_or(line)
         } else {
-            key.to_string()
+            line
         };
+        self.without_ansi(text)
+    }
 
-        if self.ignore_case {
-            normalized.to_lowercase()
+    /// `line` without escape sequences with `strip_ansi`.
+    fn without_ansi<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.strip_ansi {
+            strip_ansi(line)
         } else {
-            normalized
+            Cow::Borrowed(line)
+        }
+    }
+
+    /// Joins the first `key_words` UAX #29 words of `line` into a key, along
+    /// with where in the line the words are (see `char_span`).
+    fn unicode_words_key(&self, line: &str) -> (String, Option<usize>, Option<usize>) {
+        let mut words = line.unicode_word_indices().take(self.key_words.max(1));
+        let Some((start, first)) = words.next() else {
+            return (String::new(), None, None);
+        };
+
+        let mut key = first.to_string();
+        let mut end = start + first.len();
+        for (idx, word) in words {
+            key.push(' ');
+            key.push_str(word);
+            end = idx + word.len();
         }
+
+        let (visual_start, word_length) = char_span(line, start..end);
+        (self.prepare_key(&key), visual_start, word_length)
     }
 
-    fn compute_padding_info(&self, processed: &[ProcessedLine]) -> PaddingInfo {
-        if self.dictionary_order && !self.use_entire_line && !self.word_only {
-            // For dictionary order with right-align, we need the visual end position of the first word
+    /// Finds the first dictionary-order word of `line` at or after byte
+    /// `from`: a run starting with a `word_class` character and continuing
+    /// with those or `word_chars`. Returns its byte range.
+    fn dictionary_word(&self, line: &str, from: usize) -> Option<(usize, usize)> {
+        let (start, _) = line[from..]
+            .char_indices()
+            .find(|&(_, c)| self.word_class.contains(c))?;
+        let start = from + start;
+
+        let end = line[start..]
+            .char_indices()
+            .find(|&(_, c)| !self.word_class.contains(c) && !self.word_chars.contains(c))
+            .map_or(line.len(), |(idx, _)| start + idx);
+
+        Some((start, end))
+    }
+
+    /// Whether `prepare_key` returns every key unchanged.
+    fn prepares_verbatim(&self) -> bool {
+        !(self.normalize
+            || self.ascii_key
+            || self.ignore_nonprinting
+            || self.ignore_symbols
+            || self.trim_key
+            || self.squeeze_whitespace
+            || self.ignore_case
+            || self.phonetic.is_some())
+    }
+
+    // Helper function to prepare a key (normalize, transliterate, drop control
+    // characters and symbols, trim and squeeze whitespace, case-fold and
+    // encode phonetically if needed).
+    fn prepare_key(&self, key: &str) -> String {
+        let key = self.fold_key(key);
+        match self.phonetic {
+            Some(phonetic) => phonetic::encode(phonetic, &key),
+            None => key,
+        }
+    }
+
+    // Every step of `prepare_key` but the phonetic code, in a single pass over
+    // the characters, into a per-thread scratch buffer that is copied out at
+    // its final size.
+    fn fold_key(&self, key: &str) -> String {
+        SCRATCH.with_borrow_mut(|scratch| {
+            let mut folder = KeyFolder::new(self, scratch);
+            if self.ascii_key {
+                // Composing subsumes `normalize`. Latin letters are then
+                // decomposed to drop their marks; marks on other scripts
+                // (vowel signs, points) are part of the word and stay.
+                let mut latin_base = false;
+                for c in key.nfc() {
+                    if is_combining_mark(c) {
+                        if !latin_base {
+                            folder.push(c);
+                        }
+                        continue;
+                    }
+
+                    latin_base = is_latin(c);
+                    match ascii_spelling(c) {
+                        Some(spelled) => spelled.chars().for_each(|c| folder.push(c)),
+                        None if latin_base => decompose_canonical(c, |c| {
+                            if !is_combining_mark(c) {
+                                folder.push(c);
+                            }
+                        }),
+                        None => folder.push(c),
+                    }
+                }
+            } else if self.ignore_case && self.fold_mode == FoldMode::Canonical {
+                // Fold the decomposed key, then compose the result again
+                key.nfd().for_each(|c| folder.push(c));
+                return casefold::compose(folder.finish());
+            } else if self.normalize && is_nfc_quick(key.chars()) != IsNormalized::Yes {
+                key.nfc().for_each(|c| folder.push(c));
+            } else {
+                key.chars().for_each(|c| folder.push(c));
+            }
+            folder.finish()
+        })
+    }
+
+    /// The alignment `process_lines` returns with `right_align`, computed
+    /// for any lines (`right_align` need not be set), for custom renderers.
+    ///
+    /// With `use_end_pos`, a line is padded with `max_value` minus the end of
+    /// its key (`visual_start + word_length`) spaces, so key ends line up;
+    /// otherwise with `max_value` minus the key length in characters.
+    /// `key_width` is the longest key, for word-only output.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let lines = vec!["  fish x".to_string(), "shellfish y".to_string()];
+    /// let (processed, _) = config.process_lines(lines);
+    ///
+    /// let padding = config.compute_padding_info(&processed);
+    /// for p in &processed {
+    ///     let end = p.visual_start.unwrap() + p.word_length.unwrap();
+    ///     println!("{}{}", " ".repeat(padding.max_value - end), p.original);
+    /// }
+    /// assert_eq!(padding.max_value, 9);
+    /// ```
+    pub fn compute_padding_info<S: LineText>(&self, processed: &[ProcessedLine<S>]) -> PaddingInfo {
+        let key_width = processed
+            .par_iter()
+            .map(|p| p.key.as_ref().chars().count())
+            .max()
+            .unwrap_or(0);
+
+        if !self.word_only && self.key_fn.is_none() {
+            // Align where the key ends in the line, which is not where the
+            // line ends and may not be the key's length past the start of
+            // the line. Custom keys do not say where they came from.
             let max_end_pos = processed
                 .par_iter()
                 .filter_map(|p| p.visual_start.and_then(|s| p.word_length.map(|l| s + l)))
@@ -250,59 +1188,797 @@ impl SortConfig {
             PaddingInfo {
                 max_value: max_end_pos,
                 use_end_pos: true,
+                key_width,
             }
         } else {
-            // For other modes, just use key length
-            let max_key_len = processed
-                .par_iter()
-                .map(|p| p.key.chars().count())
-                .max()
-                .unwrap_or(0);
-
+            // For custom keys and word-only output, just use key length
             PaddingInfo {
-                max_value: max_key_len,
+                max_value: key_width,
                 use_end_pos: false,
+                key_width,
             }
         }
     }
 
-    fn sort_processed_lines(&self, processed: &mut [ProcessedLine]) {
-        // Get the string comparer
-        let string_comparer = self.get_comparer();
+    /// Compares two processed lines the way `process_lines` orders them: by
+    /// `sort_by` rank, by key using the suffix comparator, then by the
+    /// configured `tiebreak`, then by original input index.
+    ///
+    /// This is useful for merging runs of `ProcessedLine`s that were sorted
+    /// separately, e.g. when spilling to disk. Indices must be unique across
+    /// runs for the merge to reproduce the in-memory order. Group sizes for
+    /// `SortBy::Frequency` cannot be known from two lines, so that mode is
+    /// compared by suffix only.
+    pub fn compare_lines<S: LineText>(
+        &self,
+        a: &ProcessedLine<S>,
+        b: &ProcessedLine<S>,
+    ) -> Ordering {
+        self.compare_keys(a, b).then_with(|| self.break_tie(a, b))
+    }
 
-        // Create a comparator for ProcessedLine items
-        let comparator = |a: &ProcessedLine, b: &ProcessedLine| {
-            // Use the string comparer to compare the keys
-            let key_cmp = string_comparer(&a.key, &b.key);
+    /// Merges iterators over lines that are each already sorted with this
+    /// configuration into one sorted sequence, like sorting their
+    /// concatenation but without holding it in memory. Lines that compare
+    /// equal come out in the order of their iterators.
+    ///
+    /// `filter` and `exclude_no_word` still drop lines. As with
+    /// `compare_lines`, `SortBy::Frequency` is merged by suffix only.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let shards = vec![
+    ///     vec!["ba".to_string(), "b".to_string()],
+    ///     vec!["aa".to_string(), "cb".to_string()],
+    /// ];
+    ///
+    /// let merged: Vec<String> = config.merge(shards).collect();
+    /// assert_eq!(merged, ["aa", "ba", "b", "cb"]);
+    /// ```
+    pub fn merge<I>(&self, iterators: impl IntoIterator<Item = I>) -> impl Iterator<Item = String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        merge::Merge::new(self, iterators.into_iter().map(IntoIterator::into_iter))
+    }
 
-            // For equal keys, maintain original order (stable sort)
-            if key_cmp == Ordering::Equal {
-                a.index.cmp(&b.index)
-            } else {
-                key_cmp
+    /// Checks that `lines` are in the order `process_lines` would put them
+    /// in, returning the first line that sorts before the one preceding it.
+    /// Lines with equal keys may come in any order, and lines that `filter`
+    /// or `exclude_no_word` would drop are skipped. As with `compare_lines`,
+    /// `SortBy::Frequency` is checked by suffix only.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let lines = vec!["ba".to_string(), "b".to_string(), "a".to_string()];
+    ///
+    /// let disorder = config.is_sorted(&lines).unwrap_err();
+    /// assert_eq!(disorder.index, 2);
+    /// assert_eq!(disorder.previous, "b");
+    /// ```
+    pub fn is_sorted(&self, lines: &[String]) -> std::result::Result<(), Disorder> {
+        let keyed = self.extract_keys(lines.iter().map(String::as_str).collect());
+        let mut previous: Option<ProcessedLine<&str>> = None;
+
+        for line in keyed {
+            if let Some(previous) = &previous
+                && self.compare_keys(previous, &line).is_gt()
+            {
+                return Err(Disorder {
+                    index: line.index,
+                    previous: previous.original.to_string(),
+                    line: line.original.to_string(),
+                });
+            }
+            previous = Some(line);
+        }
+        Ok(())
+    }
+
+    /// Searches lines sorted with this configuration for one whose key equals
+    /// `needle`, like `slice::binary_search`: `Ok` with the position of a
+    /// match, or `Err` with where `needle` could be inserted. `needle` is
+    /// normalized and case-folded like keys are.
+    ///
+    /// Keys are compared from the end (and reversed with `reverse`), so the
+    /// search assumes `sort_by` is `SortBy::Suffix`.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let sorted = vec!["ba x".to_string(), "b y".to_string(), "ab z".to_string()];
+    ///
+    /// assert_eq!(config.binary_search(&sorted, "b"), Ok(1));
+    /// assert_eq!(config.binary_search(&sorted, "cb"), Err(3));
+    /// ```
+    pub fn binary_search(
+        &self,
+        sorted: &[String],
+        needle: &str,
+    ) -> std::result::Result<usize, usize> {
+        let needle = self.prepare_key(needle);
+        let comparer = self.get_comparer();
+        sorted.binary_search_by(|line| comparer(&self.line_key(line), &needle))
+    }
+
+    /// The number of leading lines of `sorted` whose keys sort before
+    /// `needle`, i.e. the first position `needle` could be inserted at while
+    /// keeping the order. See `binary_search` for the assumptions.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let sorted = vec!["ba".to_string(), "b".to_string(), "b".to_string(), "ab".to_string()];
+    ///
+    /// assert_eq!(config.partition_point(&sorted, "b"), 1);
+    /// assert_eq!(config.partition_point(&sorted, "ab"), 3);
+    /// ```
+    pub fn partition_point(&self, sorted: &[String], needle: &str) -> usize {
+        let needle = self.prepare_key(needle);
+        let comparer = self.get_comparer();
+        sorted.partition_point(|line| comparer(&self.line_key(line), &needle).is_lt())
+    }
+
+    /// The positions of the lines in `sorted` whose keys end with `suffix`.
+    /// In suffix order these lines are always next to each other, so this
+    /// takes O(log n) comparisons. See `binary_search` for the assumptions.
+    ///
+    /// # Example
+    /// ```
+    /// use suffixsort::SortConfig;
+    ///
+    /// let config = SortConfig::default();
+    /// let sorted: Vec<String> = ["walked", "ring", "bring", "sing", "cat"]
+    ///     .iter()
+    ///     .map(|s| s.to_string())
+    ///     .collect();
+    ///
+    /// let range = config.find_suffix_range(&sorted, "ing");
+    /// assert_eq!(&sorted[range], ["ring", "bring", "sing"]);
+    /// ```
+    pub fn find_suffix_range(&self, sorted: &[String], suffix: &str) -> Range<usize> {
+        let suffix = self.prepare_key(suffix);
+        let comparer = self.get_comparer();
+        let before_suffix = |key: &str| comparer(key, &suffix).is_lt();
+
+        let start = sorted.partition_point(|line| {
+            let key = self.line_key(line);
+            !key.ends_with(&suffix) && before_suffix(&key)
+        });
+        let end = start
+            + sorted[start..].partition_point(|line| {
+                let key = self.line_key(line);
+                key.ends_with(&suffix) || before_suffix(&key)
+            });
+        start..end
+    }
+
+    /// The key of a single line, empty if it has no word. Called once per
+    /// probe of a search, so it skips the parallel iterator `extract_keys`
+    /// sets up.
+    fn line_key(&self, line: &str) -> String {
+        self.extract_key(0, line)
+            .map_or_else(String::new, |p| p.key.into_owned())
+    }
+
+    /// Like `compare_lines` but without the tie-break: `Equal` means the
+    /// lines have equal keys and would be adjacent in the sorted output, so
+    /// this is what duplicate detection compares.
+    pub fn compare_keys<S: LineText>(
+        &self,
+        a: &ProcessedLine<S>,
+        b: &ProcessedLine<S>,
+    ) -> Ordering {
+        let rank_cmp = self.rank(a).cmp(&self.rank(b));
+        let rank_cmp = if self.reverse {
+            rank_cmp.reverse()
+        } else {
+            rank_cmp
+        };
+
+        self.compare_no_word(a, b)
+            .then(rank_cmp)
+            .then_with(|| self.get_comparer()(a.key.as_ref(), b.key.as_ref()))
+    }
+
+    /// Orders lines without a word before or after all others, as set by
+    /// `no_word_position`; `Equal` for any two lines otherwise.
+    fn compare_no_word<S: LineText>(&self, a: &ProcessedLine<S>, b: &ProcessedLine<S>) -> Ordering {
+        let has_word = |line: &ProcessedLine<S>| !line.key.as_ref().is_empty();
+        match self.no_word_position {
+            NoWordPosition::Sorted => Ordering::Equal,
+            NoWordPosition::First => has_word(a).cmp(&has_word(b)),
+            NoWordPosition::Last => has_word(b).cmp(&has_word(a)),
+        }
+    }
+
+    /// The primary sort criterion of `line` under `sort_by`, for the modes
+    /// that only need the line itself.
+    fn rank<S: LineText>(&self, line: &ProcessedLine<S>) -> usize {
+        match self.sort_by {
+            SortBy::Suffix | SortBy::Frequency => 0,
+            SortBy::KeyLength => line.key.as_ref().chars().count(),
+            SortBy::LineLength => line.original.as_ref().chars().count(),
+            SortBy::LineWidth => line.original.as_ref().width(),
+            SortBy::Syllables => match &self.syllable_fn {
+                Some(syllable_fn) => (syllable_fn.0)(line.key.as_ref()),
+                None => count_syllables(line.key.as_ref()),
+            },
+        }
+    }
+
+    /// Ranks every entry by how many lines share its key, most common first.
+    fn rank_by_frequency<S: LineText>(
+        &self,
+        processed: &[ProcessedLine<S>],
+        entries: &mut [SortEntry],
+    ) {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for p in processed {
+            *counts.entry(p.key.as_ref()).or_default() += 1;
+        }
+
+        for entry in entries {
+            entry.rank = usize::MAX - counts[processed[entry.slot].key.as_ref()];
+        }
+    }
+
+    /// Orders lines with equal keys: by the configured `tiebreak` (reversed
+    /// along with the keys), then by input index.
+    fn break_tie<S: LineText>(&self, a: &ProcessedLine<S>, b: &ProcessedLine<S>) -> Ordering {
+        let (a_line, b_line) = (a.original.as_ref(), b.original.as_ref());
+        let ordering = match self.effective_tiebreak() {
+            Tiebreak::Index => Ordering::Equal,
+            Tiebreak::Line => a_line.cmp(b_line),
+            Tiebreak::LineSuffix => a_line.chars().rev().cmp(b_line.chars().rev()),
+            Tiebreak::Rest => {
+                // Key positions count the characters keys were taken from
+                let (a_text, b_text) = (self.without_ansi(a_line), self.without_ansi(b_line));
+                rest_of_line(&a_text, a).cmp(rest_of_line(&b_text, b))
             }
         };
+        let ordering = if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
 
+        ordering.then_with(|| a.index.cmp(&b.index))
+    }
+
+    /// A stable sort only ever falls back to input order.
+    fn effective_tiebreak(&self) -> Tiebreak {
         if self.stable {
-            processed.par_sort_by(comparator);
+            Tiebreak::Index
+        } else {
+            self.tiebreak
+        }
+    }
+
+    fn sort_processed_lines<S: LineText>(&self, processed: &mut [ProcessedLine<S>]) {
+        // Work out the order first, then move each line into place once
+        let order = if self.algorithm == SortAlgorithm::Interned {
+            self.interned_order(processed)
         } else {
-            processed.par_sort_unstable_by(comparator);
+            self.arena_order(processed)
+        };
+
+        arena::apply_order(processed, order);
+
+        if self.no_word_position != NoWordPosition::Sorted {
+            // A stable sort keeps both groups in sorted order
+            processed.par_sort_by(|a, b| self.compare_no_word(a, b));
         }
     }
+
+    /// The sorted order of `processed` by sorting compact entries over a
+    /// shared key buffer, as positions in `processed`.
+    fn arena_order<S: LineText>(&self, processed: &[ProcessedLine<S>]) -> Vec<usize> {
+        let (arena, mut entries) = KeyArena::build(processed, self);
+
+        match self.sort_by {
+            SortBy::Suffix => {}
+            SortBy::Frequency => self.rank_by_frequency(processed, &mut entries),
+            _ => {
+                for entry in &mut entries {
+                    entry.rank = self.rank(&processed[entry.slot]);
+                }
+            }
+        }
+
+        if self.algorithm == SortAlgorithm::Radix && self.sort_by == SortBy::Suffix {
+            log::debug!("sorting {} lines with MSD radix sort", entries.len());
+            self.radix_sort(processed, &arena, &mut entries, 0);
+        } else {
+            if self.algorithm == SortAlgorithm::Radix {
+                log::debug!("radix sort only orders by suffix; falling back to comparison sort");
+            }
+            log::debug!(
+                "sorting {} lines with parallel {} comparison sort",
+                entries.len(),
+                if self.stable { "stable" } else { "unstable" }
+            );
+
+            // Create a comparator for the entries
+            let comparator =
+                |a: &SortEntry, b: &SortEntry| self.compare_entries(processed, &arena, a, b);
+
+            if self.stable {
+                entries.par_sort_by(comparator);
+            } else {
+                entries.par_sort_unstable_by(comparator);
+            }
+        }
+
+        entries.iter().map(|entry| entry.slot).collect()
+    }
+
+    /// The sorted order of `processed` by interned key ids (see
+    /// `SortAlgorithm::Interned`), as positions in `processed`.
+    fn interned_order<S: LineText>(&self, processed: &[ProcessedLine<S>]) -> Vec<usize> {
+        let keys = InternedKeys::new(processed.iter().map(|p| p.key.as_ref()), |a, b| {
+            self.compare_key_text(a, b)
+        });
+        log::info!(
+            "{} distinct keys among {} lines; the most common is shared by {} lines",
+            keys.counts.len(),
+            processed.len(),
+            keys.counts.iter().max().unwrap_or(&0)
+        );
+
+        // (rank, key id, slot), with group sizes known from interning
+        let mut entries: Vec<(usize, usize, usize)> = processed
+            .par_iter()
+            .zip(&keys.ids)
+            .enumerate()
+            .map(|(slot, (p, &id))| {
+                let rank = match self.sort_by {
+                    SortBy::Frequency => usize::MAX - keys.counts[id],
+                    _ => self.rank(p),
+                };
+                (rank, id, slot)
+            })
+            .collect();
+
+        log::debug!("sorting {} lines by interned key", entries.len());
+        let comparator = |a: &(usize, usize, usize), b: &(usize, usize, usize)| {
+            let ordering = (a.0, a.1).cmp(&(b.0, b.1));
+            let ordering = if self.reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then_with(|| self.break_tie(&processed[a.2], &processed[b.2]))
+        };
+        if self.stable {
+            entries.par_sort_by(comparator);
+        } else {
+            entries.par_sort_unstable_by(comparator);
+        }
+
+        entries.into_iter().map(|(_, _, slot)| slot).collect()
+    }
+
+    fn compare_entries<S: LineText>(
+        &self,
+        lines: &[ProcessedLine<S>],
+        arena: &KeyArena,
+        a: &SortEntry,
+        b: &SortEntry,
+    ) -> Ordering {
+        // Keys are stored reversed (unless compared forwards), so a bytewise
+        // comparison is a suffix comparison
+        let ordering = a
+            .rank
+            .cmp(&b.rank)
+            .then_with(|| simd::compare_bytes(arena.key(a), arena.key(b)));
+        let ordering = if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+
+        ordering.then_with(|| self.compare_ties(lines, a, b))
+    }
+
+    fn compare_ties<S: LineText>(
+        &self,
+        lines: &[ProcessedLine<S>],
+        a: &SortEntry,
+        b: &SortEntry,
+    ) -> Ordering {
+        if self.effective_tiebreak() == Tiebreak::Index {
+            // Skip the indirection through `lines` for the common case
+            a.index.cmp(&b.index)
+        } else {
+            self.break_tie(&lines[a.slot], &lines[b.slot])
+        }
+    }
+
+    /// In-place MSD (American flag) radix sort on the byte at `depth` of the
+    /// reversed keys. Buckets that get small are finished with the comparator.
+    fn radix_sort<S: LineText>(
+        &self,
+        lines: &[ProcessedLine<S>],
+        arena: &KeyArena,
+        entries: &mut [SortEntry],
+        depth: usize,
+    ) {
+        if entries.len() <= RADIX_CUTOFF {
+            entries.sort_unstable_by(|a, b| self.compare_entries(lines, arena, a, b));
+            return;
+        }
+
+        let mut counts = [0usize; RADIX_BUCKETS];
+        for entry in entries.iter() {
+            counts[self.radix_bucket(arena, entry, depth)] += 1;
+        }
+
+        let mut starts = [0usize; RADIX_BUCKETS];
+        let mut sum = 0;
+        for (start, count) in starts.iter_mut().zip(counts) {
+            *start = sum;
+            sum += count;
+        }
+
+        // Permute every entry into its bucket
+        let mut next = starts;
+        for bucket in 0..RADIX_BUCKETS {
+            let end = starts[bucket] + counts[bucket];
+            while next[bucket] < end {
+                let target = self.radix_bucket(arena, &entries[next[bucket]], depth);
+                if target != bucket {
+                    entries.swap(next[bucket], next[target]);
+                }
+                next[target] += 1;
+            }
+        }
+
+        // Split into per-bucket slices and sort those independently
+        let mut buckets = Vec::new();
+        let mut rest = entries;
+        for (bucket, count) in counts.into_iter().enumerate() {
+            let (head, tail) = rest.split_at_mut(count);
+            rest = tail;
+            if count > 1 {
+                buckets.push((bucket, head));
+            }
+        }
+
+        let exhausted = self.radix_bucket_exhausted();
+        buckets.into_par_iter().for_each(|(bucket, entries)| {
+            if bucket == exhausted {
+                // All keys in this bucket are identical; only ties remain
+                entries.sort_unstable_by(|a, b| self.compare_ties(lines, a, b));
+            } else {
+                self.radix_sort(lines, arena, entries, depth + 1);
+            }
+        });
+    }
+
+    fn radix_bucket(&self, arena: &KeyArena, entry: &SortEntry, depth: usize) -> usize {
+        // Bucket 0 holds keys that end before `depth`, so shorter keys sort first
+        let bucket = arena.key(entry).get(depth).map_or(0, |&b| b as usize + 1);
+        if self.reverse {
+            RADIX_BUCKETS - 1 - bucket
+        } else {
+            bucket
+        }
+    }
+
+    fn radix_bucket_exhausted(&self) -> usize {
+        if self.reverse { RADIX_BUCKETS - 1 } else { 0 }
+    }
 }
 
-impl Default for SortConfig {
-    fn default() -> Self {
+/// The position (in characters) of the byte range `range` of `text` and its
+/// length in characters, as `ProcessedLine::visual_start` and `word_length`.
+/// Both ends may fall inside a character.
+fn char_span(text: &str, range: Range<usize>) -> (Option<usize>, Option<usize>) {
+    let mut start = 0;
+    let mut length = 0;
+    for (i, _) in text.char_indices() {
+        if i < range.start {
+            start += 1;
+        } else if i < range.end {
+            length += 1;
+        } else {
+            break;
+        }
+    }
+    (Some(start), Some(length))
+}
+
+/// The byte offset of `part`, a slice of `text`, within `text`.
+fn offset_in(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+thread_local! {
+    /// Reused by `prepare_key` so building a key allocates only its result.
+    static SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// The per-character steps of `prepare_key` after normalization, writing
+/// into a buffer.
+struct KeyFolder<'a> {
+    config: &'a SortConfig,
+    key: &'a mut String,
+    /// Where the current run of whitespace starts in `key`, if in one
+    whitespace_start: Option<usize>,
+    /// Whether a capital sigma was kept, whose lowercase depends on context
+    has_sigma: bool,
+}
+
+impl<'a> KeyFolder<'a> {
+    fn new(config: &'a SortConfig, key: &'a mut String) -> Self {
+        key.clear();
         Self {
-            ignore_case: false,
-            use_entire_line: false,
-            dictionary_order: false,
-            reverse: false,
-            stable: false,
-            right_align: false,
-            exclude_no_word: false,
-            word_only: false,
-            normalize: false,
+            config,
+            key,
+            whitespace_start: None,
+            has_sigma: false,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        let config = self.config;
+        if (config.ignore_nonprinting && c.is_control()) || (config.ignore_symbols && is_symbol(c))
+        {
+            return;
+        }
+
+        if c.is_whitespace() {
+            match self.whitespace_start {
+                // Squeezed runs become a single space
+                Some(_) if config.squeeze_whitespace => {}
+                Some(_) => self.key.push(c),
+                None => {
+                    self.whitespace_start = Some(self.key.len());
+                    self.key
+                        .push(if config.squeeze_whitespace { ' ' } else { c });
+                }
+            }
+            return;
+        }
+
+        self.whitespace_start = None;
+        if !config.ignore_case {
+            self.key.push(c);
+        } else if config.fold_mode == FoldMode::Canonical {
+            casefold::push_folded(c, self.key);
+        } else if c == 'Σ' {
+            self.key.push(c);
+            self.has_sigma = true;
+        } else {
+            self.key.extend(c.to_lowercase());
+        }
+    }
+
+    fn finish(self) -> String {
+        if self.config.trim_key
+            && let Some(start) = self.whitespace_start
+        {
+            self.key.truncate(start);
+        }
+        if self.has_sigma {
+            // Only `str::to_lowercase` knows about final sigma
+            return self.key.to_lowercase();
+        }
+        self.key.as_str().to_owned()
+    }
+}
+
+/// The characters of `text`, `p`'s line as its key was taken from, outside
+/// the key, for `Tiebreak::Rest`.
+fn rest_of_line<'a, S: LineText>(
+    text: &'a str,
+    p: &ProcessedLine<S>,
+) -> impl Iterator<Item = char> + 'a {
+    let key = match (p.visual_start, p.word_length) {
+        (Some(start), Some(length)) => start..start + length,
+        _ => 0..0,
+    };
+    text.chars()
+        .enumerate()
+        .filter(move |(i, _)| !key.contains(i))
+        .map(|(_, c)| c)
+}
+
+/// How a Latin letter that does not decompose into an ASCII letter and
+/// combining marks is spelled in ASCII, for `ascii_key`.
+fn ascii_spelling(c: char) -> Option<&'static str> {
+    let spelled = match c {
+        'Ł' => "L",
+        'ł' => "l",
+        'Ø' => "O",
+        'ø' => "o",
+        'Đ' | 'Ð' => "D",
+        'đ' | 'ð' => "d",
+        'Ħ' => "H",
+        'ħ' => "h",
+        'ı' => "i",
+        'ŀ' => "l",
+        'Ŀ' => "L",
+        'ß' => "ss",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Þ' => "Th",
+        'þ' => "th",
+        _ => return None,
+    };
+    Some(spelled)
+}
+
+/// Whether `c` is a letter of the Latin script, whose combining marks
+/// `ascii_key` drops.
+fn is_latin(c: char) -> bool {
+    matches!(c,
+        'A'..='Z' | 'a'..='z' | '\u{aa}' | '\u{ba}'
+        | '\u{c0}'..='\u{d6}' | '\u{d8}'..='\u{f6}' | '\u{f8}'..='\u{2af}'
+        | '\u{1d00}'..='\u{1d7f}' | '\u{1e00}'..='\u{1eff}'
+        | '\u{2c60}'..='\u{2c7f}' | '\u{a720}'..='\u{a7ff}'
+        | '\u{ab30}'..='\u{ab6f}' | '\u{ff21}'..='\u{ff3a}' | '\u{ff41}'..='\u{ff5a}')
+}
+
+/// Whether `c` is a symbol (math, currency, arrows, dingbats and the like),
+/// an emoji, or a character that only modifies or joins emoji.
+fn is_symbol(c: char) -> bool {
+    matches!(c,
+        '$' | '+' | '<' | '=' | '>' | '^' | '`' | '|' | '~'
+        | '\u{a2}'..='\u{a9}' | '\u{ac}' | '\u{ae}'..='\u{b1}' | '\u{b4}' | '\u{b8}'
+        | '\u{d7}' | '\u{f7}'
+        | '\u{200d}' // zero width joiner
+        | '\u{20a0}'..='\u{20cf}' // currency symbols
+        | '\u{20d0}'..='\u{20ff}' // combining marks for symbols (keycaps)
+        | '\u{2100}'..='\u{214f}' // letterlike symbols
+        | '\u{2190}'..='\u{2bff}' // arrows, math, technical, shapes, dingbats
+        | '\u{fe00}'..='\u{fe0f}' // variation selectors
+        | '\u{1f000}'..='\u{1faff}' // emoji and pictographs
+        | '\u{e0020}'..='\u{e007f}' // emoji tag sequences
+    )
+}
+
+/// Case-folds `text` the way `SortConfig::ignore_case` folds keys with
+/// `mode`, e.g. to store search keys next to the lines they came from.
+///
+/// # Example
+/// ```
+/// use suffixsort::{FoldMode, fold_case};
+///
+/// assert_eq!(fold_case("Straße", FoldMode::Lowercase), "straße");
+/// assert_eq!(fold_case("Straße", FoldMode::Canonical), "strasse");
+/// ```
+pub fn fold_case(text: &str, mode: FoldMode) -> String {
+    match mode {
+        FoldMode::Lowercase => text.to_lowercase(),
+        FoldMode::Canonical => casefold::canonical_caseless(text),
+    }
+}
+
+/// Removes ANSI escape sequences (CSI sequences such as SGR colors and the
+/// erase-line codes `grep --color` emits) from `text`.
+///
+/// # Example
+/// ```
+/// use suffixsort::strip_ansi;
+///
+/// assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: disk"), "error: disk");
+/// ```
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            // Parameter and intermediate bytes up to the final byte
+            chars.next();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
         }
     }
+    Cow::Owned(stripped)
+}
+
+/// Compares keys in ordinary order if `forward` (whole fields with
+/// `field_compare`), and from the end otherwise.
+fn compare_text(a: &str, b: &str, forward: bool) -> Ordering {
+    if forward {
+        a.cmp(b)
+    } else {
+        compare_suffix(a, b)
+    }
+}
+
+/// Compares the numbers at the end of the keys (see `split_trailing_number`),
+/// then what comes before them, for `SortConfig::trailing_numbers`.
+fn compare_trailing_numbers(a: &str, b: &str, forward: bool) -> Ordering {
+    let (a_prefix, a_number) = split_trailing_number(a);
+    let (b_prefix, b_number) = split_trailing_number(b);
+    // Without leading zeros, a longer number is larger; equal numbers are
+    // told apart by their zeros so that distinct keys never compare equal
+    let a_number = a_number.map(|(digits, zeros)| (digits.len(), digits, zeros));
+    let b_number = b_number.map(|(digits, zeros)| (digits.len(), digits, zeros));
+
+    a_number
+        .cmp(&b_number)
+        .then_with(|| compare_text(a_prefix, b_prefix, forward))
+}
+
+/// Splits `key` at its last '.' into the name before it and the extension
+/// after it, which is `None` without a '.'.
+pub(crate) fn split_extension(key: &str) -> (&str, Option<&str>) {
+    match key.rsplit_once('.') {
+        Some((base, extension)) => (base, Some(extension)),
+        None => (key, None),
+    }
 }
+
+/// Splits the run of ASCII digits off the end of `key`, returning the rest
+/// and the digits without leading zeros along with the number of zeros
+/// (`None` without digits).
+pub(crate) fn split_trailing_number(key: &str) -> (&str, Option<(&str, usize)>) {
+    let prefix = key.trim_end_matches(|c: char| c.is_ascii_digit());
+    if prefix.len() == key.len() {
+        return (key, None);
+    }
+    let number = &key[prefix.len()..];
+    let digits = number.trim_start_matches('0');
+    (prefix, Some((digits, number.len() - digits.len())))
+}
+
+/// Compares characters in reverse order (inverse lexicographic).
+fn compare_suffix(a: &str, b: &str) -> Ordering {
+    let mut a_iter = a.chars().rev();
+    let mut b_iter = b.chars().rev();
+
+    let mut ordering = Ordering::Equal;
+    loop {
+        match (a_iter.next(), b_iter.next()) {
+            (Some(a_char), Some(b_char)) => {
+                let cmp = a_char.cmp(&b_char);
+                if cmp != Ordering::Equal {
+                    ordering = cmp;
+                    break;
+                }
+            }
+            (Some(_), None) => {
+                ordering = Ordering::Greater;
+                break;
+            }
+            (None, Some(_)) => {
+                ordering = Ordering::Less;
+                break;
+            }
+            (None, None) => break,
+        }
+    }
+
+    ordering
+}
+
+/// One bucket per byte value plus one for keys that have ended.
+const RADIX_BUCKETS: usize = 257;
+
+/// Below this many lines, a comparison sort beats another radix pass.
+const RADIX_CUTOFF: usize = 64;
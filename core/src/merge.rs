@@ -0,0 +1,103 @@
+use crate::{ProcessedLine, SortConfig};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Lines are keyed in batches of this many per input, so key extraction
+/// keeps its parallelism without reading whole inputs ahead.
+const BATCH: usize = 1024;
+
+/// A k-way merge of sorted line iterators; see `SortConfig::merge`.
+pub(crate) struct Merge<'a, I> {
+    config: &'a SortConfig,
+    inputs: Vec<Input<I>>,
+    /// The smallest unmerged line of each input that has one left
+    heads: BinaryHeap<Head<'a>>,
+}
+
+struct Input<I> {
+    lines: I,
+    keyed: VecDeque<ProcessedLine>,
+}
+
+struct Head<'a> {
+    config: &'a SortConfig,
+    /// Its `index` is the number of the input it came from, so ties resolve
+    /// in input order
+    line: ProcessedLine,
+}
+
+impl<'a, I: Iterator<Item = String>> Merge<'a, I> {
+    pub(crate) fn new(config: &'a SortConfig, inputs: impl IntoIterator<Item = I>) -> Self {
+        let mut merge = Self {
+            config,
+            inputs: inputs
+                .into_iter()
+                .map(|lines| Input {
+                    lines,
+                    keyed: VecDeque::new(),
+                })
+                .collect(),
+            heads: BinaryHeap::new(),
+        };
+
+        for i in 0..merge.inputs.len() {
+            merge.push_head(i);
+        }
+        merge
+    }
+
+    /// Moves the next line of input `i`, if any, onto the heap.
+    fn push_head(&mut self, i: usize) {
+        let config = self.config;
+        let input = &mut self.inputs[i];
+
+        // Filtered or wordless lines may leave a batch empty
+        while input.keyed.is_empty() {
+            let batch: Vec<String> = input.lines.by_ref().take(BATCH).collect();
+            if batch.is_empty() {
+                return;
+            }
+
+            input
+                .keyed
+                .extend(config.extract_keys(batch).into_iter().map(|mut line| {
+                    line.index = i;
+                    line
+                }));
+        }
+
+        let line = input.keyed.pop_front().expect("refilled above");
+        self.heads.push(Head { config, line });
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Merge<'_, I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let head = self.heads.pop()?;
+        self.push_head(head.line.index);
+        Some(head.line.original)
+    }
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` pops the greatest element; the merge wants the smallest
+        self.config.compare_lines(&other.line, &self.line)
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
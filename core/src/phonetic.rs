@@ -0,0 +1,371 @@
+//! Phonetic codes for `SortConfig::phonetic`, which replace each word of
+//! the key by (an estimate of) how it sounds, so that words spelled
+//! differently but pronounced alike sort together.
+
+/// A phonetic algorithm for `SortConfig::phonetic`.
+///
+/// # Example
+/// ```
+/// use suffixsort::{Phonetic, SortConfig};
+///
+/// let config = SortConfig {
+///     phonetic: Some(Phonetic::Rhyme),
+///     ..SortConfig::default()
+/// };
+/// let lines = ["blue", "cat", "through", "hat"].map(String::from).to_vec();
+/// let (sorted, _) = config.process_lines(lines);
+///
+/// let sorted: Vec<&str> = sorted.iter().map(|p| p.original.as_str()).collect();
+/// assert_eq!(sorted, ["blue", "through", "hat", "cat"]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phonetic {
+    /// English spelling rules that keep vowel sounds as well as consonants,
+    /// so words that rhyme get the same ending: "through", "blue" and "few"
+    /// all end in the sound "U"
+    Rhyme,
+    /// Lawrence Philips' original Metaphone: consonant sounds only, with
+    /// vowels dropped except at the start
+    Metaphone,
+    /// American Soundex: the first letter and three digits for the
+    /// following consonant groups, e.g. "R163" for "Robert"
+    Soundex,
+}
+
+/// Replaces every run of letters in `key` by its code under `phonetic`,
+/// keeping the characters between runs.
+pub(crate) fn encode(phonetic: Phonetic, key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    let mut word = String::new();
+    for c in key.chars() {
+        if c.is_alphabetic() {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+        encode_word(phonetic, &word, &mut encoded);
+        word.clear();
+        encoded.push(c);
+    }
+    encode_word(phonetic, &word, &mut encoded);
+    encoded
+}
+
+fn encode_word(phonetic: Phonetic, word: &str, out: &mut String) {
+    if word.is_empty() {
+        return;
+    }
+    let word: Vec<char> = word.chars().collect();
+    match phonetic {
+        Phonetic::Rhyme => rhyme(&word, out),
+        Phonetic::Metaphone => metaphone(&word, out),
+        Phonetic::Soundex => soundex(&word, out),
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// Where a `RHYME_RULES` pattern must be found in the word.
+#[derive(Clone, Copy, PartialEq)]
+enum At {
+    Start,
+    End,
+    Anywhere,
+}
+
+/// Spellings and the sounds they stand for, tried in order at each position
+/// of a word. Consonant sounds are lowercase (`x` is "sh", `0` is "th", `c`
+/// is "ch"); vowel sounds are short (lowercase) or long (uppercase), or one
+/// of the diphthongs `Au` ("out"), `Oi` ("boy") and `Aw` ("saw").
+const RHYME_RULES: &[(&str, &str, At)] = &[
+    ("kn", "n", At::Start),
+    ("gn", "n", At::Start),
+    ("wr", "r", At::Start),
+    ("ps", "s", At::Start),
+    ("wh", "w", At::Start),
+    ("through", "0rU", At::Start),
+    ("rough", "ruf", At::End),
+    ("tough", "tuf", At::End),
+    ("nough", "nuf", At::End),
+    ("cough", "kof", At::End),
+    ("ought", "Awt", At::End),
+    ("ough", "O", At::End),
+    ("eigh", "A", At::Anywhere),
+    ("augh", "Aw", At::Anywhere),
+    ("igh", "I", At::Anywhere),
+    ("tch", "c", At::Anywhere),
+    ("dge", "j", At::Anywhere),
+    ("mb", "m", At::End),
+    ("ie", "I", At::End),
+    ("oe", "O", At::End),
+    ("ow", "O", At::End),
+    ("ey", "E", At::End),
+    ("ai", "A", At::Anywhere),
+    ("ay", "A", At::Anywhere),
+    ("ee", "E", At::Anywhere),
+    ("ea", "E", At::Anywhere),
+    ("ie", "E", At::Anywhere),
+    ("ei", "E", At::Anywhere),
+    ("oa", "O", At::Anywhere),
+    ("oo", "U", At::Anywhere),
+    ("ue", "U", At::Anywhere),
+    ("ew", "U", At::Anywhere),
+    ("ui", "U", At::Anywhere),
+    ("ou", "Au", At::Anywhere),
+    ("ow", "Au", At::Anywhere),
+    ("oi", "Oi", At::Anywhere),
+    ("oy", "Oi", At::Anywhere),
+    ("au", "Aw", At::Anywhere),
+    ("aw", "Aw", At::Anywhere),
+    ("ch", "c", At::Anywhere),
+    ("sh", "x", At::Anywhere),
+    ("ph", "f", At::Anywhere),
+    ("th", "0", At::Anywhere),
+    ("ck", "k", At::Anywhere),
+    ("gh", "", At::Anywhere),
+    ("qu", "kw", At::Anywhere),
+    ("ce", "s", At::End),
+    ("ci", "si", At::Anywhere),
+    ("cy", "sE", At::End),
+    ("ge", "j", At::End),
+    ("gi", "ji", At::Anywhere),
+    ("x", "ks", At::Anywhere),
+    ("q", "k", At::Anywhere),
+    ("c", "k", At::Anywhere),
+];
+
+/// Sounds out `word` (lowercase) with `RHYME_RULES`, a final "y", "o" and
+/// silent "e", and long vowels before a consonant and a silent "e" ("name").
+fn rhyme(word: &[char], out: &mut String) {
+    let start = out.len();
+    let len = word.len();
+    // "name", "time": the vowel before a final consonant and "e" is long
+    let magic_e = len >= 3
+        && word[len - 1] == 'e'
+        && !is_vowel(word[len - 2])
+        && is_vowel(word[len - 3])
+        && (len == 3 || !is_vowel(word[len - 4]));
+
+    let mut i = 0;
+    while i < len {
+        let rest = &word[i..];
+        let rule = RHYME_RULES.iter().find(|(pattern, _, at)| {
+            let n = pattern.chars().count();
+            rest.len() >= n
+                && rest.iter().take(n).copied().eq(pattern.chars())
+                && match at {
+                    At::Start => i == 0,
+                    At::End => n == rest.len(),
+                    At::Anywhere => true,
+                }
+        });
+        if let Some((pattern, sound, _)) = rule {
+            push_sound(out, start, sound);
+            i += pattern.chars().count();
+            continue;
+        }
+
+        let c = word[i];
+        let last = i == len - 1;
+        match c {
+            // A final "e" is silent unless it is the only vowel ("be")
+            'e' if last && i > 0 => {
+                if !word[..i].iter().any(|&c| is_vowel(c)) {
+                    push_sound(out, start, "E");
+                }
+            }
+            'y' if last && i > 0 => {
+                // "fly" but "happy"
+                let other_vowels = word[..i].iter().any(|&c| is_vowel(c));
+                push_sound(out, start, if other_vowels { "E" } else { "I" });
+            }
+            'o' if last && i > 0 => push_sound(out, start, "O"),
+            c if is_vowel(c) && magic_e && i == len - 3 => {
+                push_sound(out, start, &c.to_uppercase().to_string())
+            }
+            c => push_sound(out, start, c.encode_utf8(&mut [0; 4])),
+        }
+        i += 1;
+    }
+}
+
+/// Appends `sound` to the code started at `start` in `out`, skipping a
+/// consonant that repeats the one before ("butter").
+fn push_sound(out: &mut String, start: usize, sound: &str) {
+    let mut chars = sound.chars();
+    if let (Some(first), Some(previous)) = (chars.clone().next(), out[start..].chars().last())
+        && first == previous
+        && first.is_ascii_lowercase()
+        && !is_vowel(first)
+    {
+        chars.next();
+    }
+    out.extend(chars);
+}
+
+/// Appends the Metaphone code of `word` (lowercase), in uppercase.
+fn metaphone(word: &[char], out: &mut String) {
+    let at = |i: usize| word.get(i).copied().unwrap_or('\0');
+    let len = word.len();
+    let mut i = 0;
+
+    // Initial letters that are silent or change; a vowel is only kept first
+    match (at(0), at(1)) {
+        ('a', 'e') | ('g', 'n') | ('k', 'n') | ('p', 'n') | ('w', 'r') => i = 1,
+        ('x', _) => {
+            out.push('S');
+            i = 1;
+        }
+        ('w', 'h') => {
+            out.push('W');
+            i = 2;
+        }
+        _ => {}
+    }
+    let first = i;
+
+    while i < len {
+        let c = word[i];
+        let (prev, next, after) = (if i > 0 { at(i - 1) } else { '\0' }, at(i + 1), at(i + 2));
+        // Doubled letters count once, except "cc" ("accident")
+        if c == prev && c != 'c' {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            'a' | 'e' | 'i' | 'o' | 'u' => {
+                if i == first {
+                    out.extend(c.to_uppercase());
+                }
+            }
+            'b' => {
+                if !(prev == 'm' && i == len - 1) {
+                    out.push('B');
+                }
+            }
+            'c' => {
+                if next == 'i' && after == 'a' {
+                    out.push('X');
+                } else if next == 'h' {
+                    out.push(if prev == 's' { 'K' } else { 'X' });
+                    i += 1;
+                } else if matches!(next, 'i' | 'e' | 'y') {
+                    if prev != 's' {
+                        out.push('S');
+                    }
+                } else {
+                    out.push('K');
+                }
+            }
+            'd' => {
+                if next == 'g' && matches!(after, 'e' | 'y' | 'i') {
+                    out.push('J');
+                    i += 1;
+                } else {
+                    out.push('T');
+                }
+            }
+            'g' => {
+                let silent_gh = next == 'h' && !is_vowel(after);
+                let silent_gn = next == 'n' && (i + 2 == len || (after == 'e' && at(i + 3) == 'd'));
+                if !silent_gh && !silent_gn {
+                    out.push(if matches!(next, 'i' | 'e' | 'y') {
+                        'J'
+                    } else {
+                        'K'
+                    });
+                }
+            }
+            'h' => {
+                let after_vowel_only = is_vowel(prev) && !is_vowel(next);
+                if !after_vowel_only && !matches!(prev, 'c' | 's' | 'p' | 't' | 'g') {
+                    out.push('H');
+                }
+            }
+            'k' => {
+                if prev != 'c' {
+                    out.push('K');
+                }
+            }
+            'p' => {
+                if next == 'h' {
+                    out.push('F');
+                    i += 1;
+                } else {
+                    out.push('P');
+                }
+            }
+            'q' => out.push('K'),
+            's' => {
+                if next == 'h' {
+                    out.push('X');
+                    i += 1;
+                } else if next == 'i' && matches!(after, 'o' | 'a') {
+                    out.push('X');
+                } else {
+                    out.push('S');
+                }
+            }
+            't' => {
+                if next == 'i' && matches!(after, 'o' | 'a') {
+                    out.push('X');
+                } else if next == 'h' {
+                    out.push('0');
+                    i += 1;
+                } else if !(next == 'c' && after == 'h') {
+                    out.push('T');
+                }
+            }
+            'v' => out.push('F'),
+            'w' | 'y' => {
+                if is_vowel(next) {
+                    out.extend(c.to_uppercase());
+                }
+            }
+            'x' => out.push_str("KS"),
+            'z' => out.push('S'),
+            'f' | 'j' | 'l' | 'm' | 'n' | 'r' => out.extend(c.to_uppercase()),
+            // Letters of other alphabets are kept
+            c => out.push(c),
+        }
+        i += 1;
+    }
+}
+
+/// Appends the Soundex code of `word` (lowercase).
+fn soundex(word: &[char], out: &mut String) {
+    let digit = |c: char| match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+
+    out.extend(word[0].to_uppercase());
+    let mut digits = 0;
+    let mut previous = digit(word[0]);
+    for &c in &word[1..] {
+        if digits == 3 {
+            break;
+        }
+        let code = digit(c);
+        if let Some(d) = code
+            && code != previous
+        {
+            out.push(d);
+            digits += 1;
+        }
+        // "h" and "w" do not separate letters with the same code
+        if !matches!(c, 'h' | 'w') {
+            previous = code;
+        }
+    }
+    for _ in digits..3 {
+        out.push('0');
+    }
+}
@@ -0,0 +1,75 @@
+//! Fast bytewise comparison for keys with long common prefixes.
+//!
+//! Keys are stored reversed, so a long common suffix in the original keys is
+//! a long common prefix here. Finding the first differing byte a vector at a
+//! time keeps comparisons of words like "-ization" cheap.
+
+use std::cmp::Ordering;
+
+/// Compares two byte strings lexicographically, like `Ord for [u8]`.
+#[inline]
+pub(crate) fn compare_bytes(a: &[u8], b: &[u8]) -> Ordering {
+    let len = a.len().min(b.len());
+    let i = first_mismatch(&a[..len], &b[..len]);
+
+    if i < len {
+        a[i].cmp(&b[i])
+    } else {
+        a.len().cmp(&b.len())
+    }
+}
+
+/// Returns the index of the first differing byte of two equal-length slices,
+/// or their length if they are equal.
+#[inline]
+fn first_mismatch(a: &[u8], b: &[u8]) -> usize {
+    debug_assert_eq!(a.len(), b.len());
+
+    #[cfg(target_arch = "x86_64")]
+    let start = sse2_common_prefix(a, b);
+    #[cfg(not(target_arch = "x86_64"))]
+    let start = 0;
+
+    let mut i = start;
+
+    // Compare the rest a word at a time
+    while i + 8 <= a.len() {
+        let x = u64::from_le_bytes(a[i..i + 8].try_into().unwrap());
+        let y = u64::from_le_bytes(b[i..i + 8].try_into().unwrap());
+        let diff = x ^ y;
+        if diff != 0 {
+            return i + (diff.trailing_zeros() / 8) as usize;
+        }
+        i += 8;
+    }
+
+    while i < a.len() && a[i] == b[i] {
+        i += 1;
+    }
+    i
+}
+
+/// Skips over equal 16-byte blocks with SSE2 (always available on x86_64),
+/// returning the index of the first block that differs or the start of the
+/// remaining tail.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn sse2_common_prefix(a: &[u8], b: &[u8]) -> usize {
+    use std::arch::x86_64::{__m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8};
+
+    let mut i = 0;
+    while i + 16 <= a.len() {
+        // SAFETY: SSE2 is part of the x86_64 baseline, `i + 16 <= len` for
+        // both slices, and loadu has no alignment requirement
+        let equal = unsafe {
+            let x = _mm_loadu_si128(a.as_ptr().add(i) as *const __m128i);
+            let y = _mm_loadu_si128(b.as_ptr().add(i) as *const __m128i);
+            _mm_movemask_epi8(_mm_cmpeq_epi8(x, y)) as u32
+        };
+        if equal != 0xffff {
+            return i + (!equal).trailing_zeros() as usize;
+        }
+        i += 16;
+    }
+    i
+}
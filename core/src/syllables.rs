@@ -0,0 +1,65 @@
+/// Estimates the number of syllables in `text` for `SortBy::Syllables`, by
+/// counting groups of vowels in each word. This works reasonably for English
+/// (a silent final "e" is not counted) and other languages written with
+/// Latin letters; set `SortConfig::syllable_fn` for better estimates.
+///
+/// # Example
+/// ```
+/// use suffixsort::count_syllables;
+///
+/// assert_eq!(count_syllables("name"), 1);
+/// assert_eq!(count_syllables("table"), 2);
+/// assert_eq!(count_syllables("beautiful day"), 4);
+/// ```
+pub fn count_syllables(text: &str) -> usize {
+    text.split(|c: char| !c.is_alphabetic())
+        .map(word_syllables)
+        .sum()
+}
+
+fn word_syllables(word: &str) -> usize {
+    if word.is_empty() {
+        return 0;
+    }
+
+    let chars: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+    let mut groups = 0;
+    let mut in_group = false;
+    for &c in &chars {
+        let vowel = is_vowel(c);
+        if vowel && !in_group {
+            groups += 1;
+        }
+        in_group = vowel;
+    }
+
+    // A final "e" after a consonant is silent ("name"), unless it follows a
+    // consonant and "l" ("table")
+    if let [.., before, last, 'e'] = chars[..]
+        && groups > 1
+        && !is_vowel(last)
+        && (last != 'l' || is_vowel(before))
+    {
+        groups -= 1;
+    }
+    groups.max(1)
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(
+        c,
+        'a' | 'e'
+            | 'i'
+            | 'o'
+            | 'u'
+            | 'y'
+            | 'à'..='å'
+            | 'è'..='ï'
+            | 'ò'..='ö'
+            | 'ø'..='ü'
+            | 'ý'
+            | 'ÿ'
+            | 'æ'
+            | 'œ'
+    )
+}
@@ -0,0 +1,59 @@
+#ifndef SUFFIXSORT_H
+#define SUFFIXSORT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes */
+#define SSORT_OK          0
+#define SSORT_ERR_NULL   (-1)
+#define SSORT_ERR_UTF8   (-2)
+#define SSORT_ERR_OPTION (-3)
+
+/* Options for ssort_config_set */
+#define SSORT_IGNORE_CASE      0u
+#define SSORT_USE_ENTIRE_LINE  1u
+#define SSORT_DICTIONARY_ORDER 2u
+#define SSORT_REVERSE          3u
+#define SSORT_STABLE           4u
+#define SSORT_EXCLUDE_NO_WORD  5u
+#define SSORT_WORD_ONLY        6u
+#define SSORT_NORMALIZE        7u
+
+typedef struct SsortConfig ssort_config;
+
+/*
+ * Creates a configuration with all options disabled. Lines with equal keys
+ * are ordered by the whole line, as by default in ssort.
+ */
+ssort_config *ssort_config_new(void);
+
+/* Releases a configuration created by ssort_config_new. */
+void ssort_config_free(ssort_config *config);
+
+/* Enables or disables one of the SSORT_* options. */
+int ssort_config_set(ssort_config *config, unsigned int option, bool enabled);
+
+/*
+ * Sorts `len` NUL-terminated UTF-8 lines. On success, stores a newly
+ * allocated array in `*out_lines` and its length in `*out_len`; release it
+ * with ssort_lines_free.
+ */
+int ssort_sort(const ssort_config *config, const char *const *lines, size_t len,
+               char ***out_lines, size_t *out_len);
+
+/* Releases an array returned by ssort_sort. */
+void ssort_lines_free(char **lines, size_t len);
+
+/* Suffix comparison of two strings; returns <0, 0 or >0 like strcmp. */
+int ssort_compare(const ssort_config *config, const char *a, const char *b);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SUFFIXSORT_H */
@@ -0,0 +1,193 @@
+//! C bindings for the suffixsort library.
+//!
+//! The matching header lives in `include/suffixsort.h`. All strings crossing
+//! the boundary are NUL-terminated UTF-8; arrays returned by the library must
+//! be released with `ssort_lines_free`.
+
+use std::cmp::Ordering;
+use std::ffi::{CStr, CString, c_char, c_int, c_uint};
+use std::ptr;
+use suffixsort::{SortConfig, Tiebreak};
+
+pub const SSORT_OK: c_int = 0;
+pub const SSORT_ERR_NULL: c_int = -1;
+pub const SSORT_ERR_UTF8: c_int = -2;
+pub const SSORT_ERR_OPTION: c_int = -3;
+
+pub const SSORT_IGNORE_CASE: c_uint = 0;
+pub const SSORT_USE_ENTIRE_LINE: c_uint = 1;
+pub const SSORT_DICTIONARY_ORDER: c_uint = 2;
+pub const SSORT_REVERSE: c_uint = 3;
+pub const SSORT_STABLE: c_uint = 4;
+pub const SSORT_EXCLUDE_NO_WORD: c_uint = 5;
+pub const SSORT_WORD_ONLY: c_uint = 6;
+pub const SSORT_NORMALIZE: c_uint = 7;
+
+/// Opaque configuration handle (`ssort_config` on the C side).
+pub struct SsortConfig(SortConfig);
+
+/// Creates a configuration with all options disabled. Lines with equal keys
+/// are ordered by the whole line, as by default in ssort.
+///
+/// The returned handle must be released with `ssort_config_free`.
+#[unsafe(no_mangle)]
+pub extern "C" fn ssort_config_new() -> *mut SsortConfig {
+    Box::into_raw(Box::new(SsortConfig(SortConfig {
+        // The default of ssort's --tiebreak, not of `SortConfig`
+        tiebreak: Tiebreak::Line,
+        ..SortConfig::default()
+    })))
+}
+
+/// Releases a configuration created by `ssort_config_new`.
+///
+/// # Safety
+///
+/// `config` must be null or a pointer returned by `ssort_config_new` that has
+/// not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ssort_config_free(config: *mut SsortConfig) {
+    if !config.is_null() {
+        drop(unsafe { Box::from_raw(config) });
+    }
+}
+
+/// Enables or disables one of the `SSORT_*` options.
+///
+/// # Safety
+///
+/// `config` must be null or a valid pointer returned by `ssort_config_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ssort_config_set(
+    config: *mut SsortConfig,
+    option: c_uint,
+    enabled: bool,
+) -> c_int {
+    let Some(SsortConfig(config)) = (unsafe { config.as_mut() }) else {
+        return SSORT_ERR_NULL;
+    };
+
+    let field = match option {
+        SSORT_IGNORE_CASE => &mut config.ignore_case,
+        SSORT_USE_ENTIRE_LINE => &mut config.use_entire_line,
+        SSORT_DICTIONARY_ORDER => &mut config.dictionary_order,
+        SSORT_REVERSE => &mut config.reverse,
+        SSORT_STABLE => &mut config.stable,
+        SSORT_EXCLUDE_NO_WORD => &mut config.exclude_no_word,
+        SSORT_WORD_ONLY => &mut config.word_only,
+        SSORT_NORMALIZE => &mut config.normalize,
+        _ => return SSORT_ERR_OPTION,
+    };
+    *field = enabled;
+
+    SSORT_OK
+}
+
+/// Sorts `len` lines and stores a newly allocated array of sorted lines in
+/// `out_lines` and its length in `out_len`.
+///
+/// With `SSORT_WORD_ONLY`, the sort keys are returned instead of the lines.
+/// The output may be shorter than the input when `SSORT_EXCLUDE_NO_WORD` is
+/// set. Release the result with `ssort_lines_free`.
+///
+/// # Safety
+///
+/// `config` must be a valid configuration handle, `lines` must point to `len`
+/// valid NUL-terminated strings, and `out_lines`/`out_len` must be valid for
+/// writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ssort_sort(
+    config: *const SsortConfig,
+    lines: *const *const c_char,
+    len: usize,
+    out_lines: *mut *mut *mut c_char,
+    out_len: *mut usize,
+) -> c_int {
+    let Some(SsortConfig(config)) = (unsafe { config.as_ref() }) else {
+        return SSORT_ERR_NULL;
+    };
+    if (lines.is_null() && len > 0) || out_lines.is_null() || out_len.is_null() {
+        return SSORT_ERR_NULL;
+    }
+
+    let mut input = Vec::with_capacity(len);
+    for i in 0..len {
+        let line = unsafe { *lines.add(i) };
+        if line.is_null() {
+            return SSORT_ERR_NULL;
+        }
+        match unsafe { CStr::from_ptr(line) }.to_str() {
+            Ok(line) => input.push(line.to_string()),
+            Err(_) => return SSORT_ERR_UTF8,
+        }
+    }
+
+    let (processed, _) = config.process_lines(input);
+
+    // Neither lines nor keys can contain NUL bytes, as they come from C strings
+    let sorted: Box<[*mut c_char]> = processed
+        .into_iter()
+        .map(|p| if config.word_only { p.key } else { p.original })
+        .map(|s| CString::new(s).map_or(ptr::null_mut(), CString::into_raw))
+        .collect();
+
+    unsafe {
+        *out_len = sorted.len();
+        *out_lines = Box::into_raw(sorted) as *mut *mut c_char;
+    }
+
+    SSORT_OK
+}
+
+/// Releases an array returned by `ssort_sort`.
+///
+/// # Safety
+///
+/// `lines` and `len` must be exactly the values returned by `ssort_sort`, and
+/// the array must not have been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ssort_lines_free(lines: *mut *mut c_char, len: usize) {
+    if lines.is_null() {
+        return;
+    }
+
+    let lines = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(lines, len)) };
+    for &line in lines.iter() {
+        if !line.is_null() {
+            drop(unsafe { CString::from_raw(line) });
+        }
+    }
+}
+
+/// Compares two strings with the suffix comparator, returning a negative
+/// value, zero, or a positive value like `strcmp`.
+///
+/// As with `SortConfig::get_comparer`, only `SSORT_REVERSE` affects the
+/// result; case folding and normalization are not applied. Invalid UTF-8 is
+/// replaced with U+FFFD before comparing.
+///
+/// # Safety
+///
+/// `config` must be a valid configuration handle and `a` and `b` must be
+/// valid NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ssort_compare(
+    config: *const SsortConfig,
+    a: *const c_char,
+    b: *const c_char,
+) -> c_int {
+    let (Some(SsortConfig(config)), false, false) =
+        (unsafe { config.as_ref() }, a.is_null(), b.is_null())
+    else {
+        return 0;
+    };
+
+    let a = unsafe { CStr::from_ptr(a) }.to_string_lossy();
+    let b = unsafe { CStr::from_ptr(b) }.to_string_lossy();
+
+    match config.get_comparer()(&a, &b) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+use suffixsort::{SortConfig, Tiebreak};
+use wasm_bindgen::prelude::*;
+
+/// Options accepted by `sortLines`, mirroring the fields of `SortConfig`.
+///
+/// Keys are camelCase on the JavaScript side and every key is optional:
+///
+/// ```js
+/// sortLines(lines, { ignoreCase: true, dictionaryOrder: true });
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct SortOptions {
+    ignore_case: bool,
+    use_entire_line: bool,
+    dictionary_order: bool,
+    reverse: bool,
+    stable: bool,
+    exclude_no_word: bool,
+    word_only: bool,
+    normalize: bool,
+}
+
+impl From<SortOptions> for SortConfig {
+    fn from(options: SortOptions) -> Self {
+        SortConfig {
+            ignore_case: options.ignore_case,
+            use_entire_line: options.use_entire_line,
+            dictionary_order: options.dictionary_order,
+            reverse: options.reverse,
+            stable: options.stable,
+            exclude_no_word: options.exclude_no_word,
+            word_only: options.word_only,
+            normalize: options.normalize,
+            // The default of ssort's --tiebreak, not of `SortConfig`
+            tiebreak: Tiebreak::Line,
+            ..SortConfig::default()
+        }
+    }
+}
+
+/// Sorts `lines` in inverse lexicographic order, using the same processing
+/// and comparison logic as the `ssort` command-line tool.
+///
+/// Returns the original lines in sorted order, or only the sort keys when
+/// `wordOnly` is set. Alignment is left to the caller (e.g. CSS
+/// `text-align: right`), so `rightAlign` is not accepted here.
+#[wasm_bindgen(js_name = sortLines)]
+pub fn sort_lines(lines: Vec<String>, options: JsValue) -> Result<Vec<String>, JsError> {
+    let options: SortOptions = if options.is_undefined() || options.is_null() {
+        SortOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+    let config = SortConfig::from(options);
+
+    let (processed, _) = config.process_lines(lines);
+
+    let sorted = if config.word_only {
+        processed.into_iter().map(|p| p.key).collect()
+    } else {
+        processed.into_iter().map(|p| p.original).collect()
+    };
+
+    Ok(sorted)
+}